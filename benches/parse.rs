@@ -0,0 +1,124 @@
+//! Parse and tokenize generated documents of doubling sizes, and print the
+//! time per byte, which should stay about the same if parsing scales linearly.
+//!
+//! Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use html_query_parser::{parse_with, Limits, ParseOptions, Tokenizer};
+
+type Generate = fn(usize) -> String;
+
+// Many small siblings, like a long article.
+fn flat(n: usize) -> String {
+    r#"<p class="text">Hello, <b>world</b> &amp; <a href="/">home</a>!</p>"#.repeat(n)
+}
+
+// Elements nested `n` levels deep, all closed by their end tags.
+fn nested(n: usize) -> String {
+    "<div>".repeat(n) + &"</div>".repeat(n)
+}
+
+// Elements nested `n` levels deep, which are only closed at the end.
+fn unclosed(n: usize) -> String {
+    "<section><span>".repeat(n)
+}
+
+// Elements nested `n` levels deep in a `button`, where each of them looks
+// for a `p` to close, which the `button` hides.
+fn button(n: usize) -> String {
+    "<p><button>".to_string() + &"<div>".repeat(n)
+}
+
+// Elements nested `n` levels deep, followed by end tags that match none of
+// them.
+fn stray(n: usize) -> String {
+    "<div>".repeat(n) + &"</x>".repeat(n)
+}
+
+// Paragraphs and list items whose end tags are omitted.
+fn implied(n: usize) -> String {
+    "<p>One<ul><li>Two<li>Three</ul>".repeat(n)
+}
+
+// Large tables with omitted end tags of cells and rows.
+fn table(n: usize) -> String {
+    format!("<table>{}</table>", "<tr><td>1<td>2<th>3".repeat(n))
+}
+
+// A large script, whose content is raw text.
+fn script(n: usize) -> String {
+    format!(
+        "<script>{}</script>",
+        "if (a < b) { c = '</div>'; }\n".repeat(n)
+    )
+}
+
+// Run `parse_with` a few times and take the fastest, without the limit on
+// the depth, so that the deeply nested corpora are parsed in full. Dropping
+// the dom still recurses once per level, which the up to 32000 levels of
+// them fit in the stack of the main thread.
+fn measure_parse(html: &str) -> Duration {
+    let options = ParseOptions {
+        limits: Limits {
+            max_depth: usize::MAX,
+            ..Limits::default()
+        },
+        ..ParseOptions::default()
+    };
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            let nodes = parse_with(html, &options);
+            let elapsed = start.elapsed();
+            drop(nodes);
+            elapsed
+        })
+        .min()
+        .unwrap()
+}
+
+// Run the `Tokenizer` through the html a few times and take the fastest.
+fn measure_tokenize(html: &str) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            Tokenizer::new(html).for_each(drop);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let corpus: [(&str, Generate); 8] = [
+        ("flat", flat),
+        ("nested", nested),
+        ("unclosed", unclosed),
+        ("button", button),
+        ("stray", stray),
+        ("implied", implied),
+        ("table", table),
+        ("script", script),
+    ];
+    println!(
+        "{:<10} {:>8} {:>10} {:>12} {:>10} {:>12}",
+        "corpus", "n", "bytes", "time", "ns/byte", "tokenize"
+    );
+    for (name, generate) in corpus {
+        for n in [1_000, 2_000, 4_000, 8_000, 16_000] {
+            let html = generate(n);
+            let elapsed = measure_parse(&html);
+            let ns_per_byte = |elapsed: Duration| elapsed.as_nanos() as f64 / html.len() as f64;
+            println!(
+                "{:<10} {:>8} {:>10} {:>12.2?} {:>10.2} {:>12.2}",
+                name,
+                n,
+                html.len(),
+                elapsed,
+                ns_per_byte(elapsed),
+                ns_per_byte(measure_tokenize(&html))
+            );
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::str;
+
+use crate::{parse::charset_in_content, query::to_element, Element, Namespace, Node};
+
+/// Html document parsed by [`parse_document`](crate::parse_document),
+/// which always has the `html` element with `head` and `body` in it.
+///
+/// The elements are returned as copies like
+/// [`Queryable::query`](crate::Queryable::query) does. Since
+/// [`html()`](crate::Htmlifiable::html) converts the document to html, the
+/// `html` element is returned by [`html_element`](Document::html_element).
+///
+/// ```
+/// use html_query_parser::{parse_document, Htmlifiable};
+///
+/// let mut document = parse_document(r#"<html lang="en"><title> My
+///     App </title><p>Hello</p>"#);
+/// assert_eq!(document.lang(), Some("en"));
+/// assert_eq!(document.title().unwrap(), "My App");
+///
+/// document.set_title("Hello");
+/// assert_eq!(
+///     document.html(),
+///     r#"<html lang="en"><head><title>Hello</title></head><body><p>Hello</p></body></html>"#
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Document<'a> {
+    /// The top-level nodes, which are the `html` element and the doctype
+    /// and comments before it.
+    pub nodes: Vec<Node<'a>>,
+}
+
+// Check if the node is the html element `<name>`.
+fn is_element(node: &Node<'_>, name: &str) -> bool {
+    matches!(
+        node,
+        Node::Element { name: n, namespace: Namespace::Html, .. } if n.eq_ignore_ascii_case(name)
+    )
+}
+
+fn children<'n, 'a>(node: &'n Node<'a>) -> &'n [Node<'a>] {
+    match node {
+        Node::Element { children, .. } => children,
+        _ => &[],
+    }
+}
+
+fn attr<'n>(node: &'n Node<'_>, key: &str) -> Option<&'n str> {
+    match node {
+        Node::Element { attrs, .. } => attrs.get(key).map(|value| value.as_ref()),
+        _ => None,
+    }
+}
+
+// Find the first node in tree order that `matches`, without recursion.
+fn find<'n, 'a>(
+    nodes: &'n [Node<'a>],
+    matches: impl Fn(&Node<'a>) -> bool,
+) -> Option<&'n Node<'a>> {
+    let mut stack = vec![nodes.iter()];
+    while let Some(nodes) = stack.last_mut() {
+        match nodes.next() {
+            Some(node) if matches(node) => return Some(node),
+            Some(node) => stack.push(children(node).iter()),
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    None
+}
+
+// Find the first node in tree order that `matches` like `find`, to edit it.
+fn find_mut<'n, 'a>(
+    nodes: &'n mut [Node<'a>],
+    matches: impl Fn(&Node<'a>) -> bool,
+) -> Option<&'n mut Node<'a>> {
+    let mut stack = vec![nodes.iter_mut()];
+    while let Some(nodes) = stack.last_mut() {
+        match nodes.next() {
+            Some(node) if matches(node) => return Some(node),
+            Some(Node::Element { children, .. }) => stack.push(children.iter_mut()),
+            Some(_) => {}
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    None
+}
+
+// Get the charset declared by the `<meta>`, if any.
+fn meta_charset<'n>(meta: &'n Node<'_>) -> Option<&'n str> {
+    if let Some(charset) = attr(meta, "charset") {
+        return Some(charset.trim());
+    }
+    let is_content_type = attr(meta, "http-equiv")
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("content-type"));
+    if !is_content_type {
+        return None;
+    }
+    let charset = charset_in_content(attr(meta, "content")?.as_bytes())?;
+    // The label is cut off at ascii bytes, so it is still valid UTF-8.
+    str::from_utf8(charset).ok()
+}
+
+impl<'a> Document<'a> {
+    /// Get the doctype node, if any.
+    pub fn doctype(&self) -> Option<&Node<'a>> {
+        self.nodes
+            .iter()
+            .find(|node| matches!(node, Node::Doctype { .. }))
+    }
+
+    /// Get the `html` element.
+    pub fn html_element(&self) -> Option<Element<'a>> {
+        self.html_node().map(to_element)
+    }
+
+    /// Get the `head` element.
+    pub fn head(&self) -> Option<Element<'a>> {
+        self.html_child("head").map(to_element)
+    }
+
+    /// Get the `body` element.
+    pub fn body(&self) -> Option<Element<'a>> {
+        self.html_child("body").map(to_element)
+    }
+
+    /// Get the text of the first `title` element, with the whitespace
+    /// around it stripped and the whitespace inside it collapsed, like
+    /// `document.title` in browsers.
+    pub fn title(&self) -> Option<String> {
+        let title = find(&self.nodes, |node| is_element(node, "title"))?;
+        let mut text = String::new();
+        for node in children(title) {
+            if let Node::Text { text: t, .. } = node {
+                text.push_str(t);
+            }
+        }
+        Some(text.split_ascii_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Set the text of the first `title` element, or add a `title` element
+    /// to `head` if there is none.
+    pub fn set_title(&mut self, title: &str) {
+        let text = Node::new_text(title);
+        if let Some(Node::Element { children, .. }) =
+            find_mut(&mut self.nodes, |node| is_element(node, "title"))
+        {
+            *children = vec![text];
+            return;
+        }
+        let html = self.nodes.iter_mut().find(|node| is_element(node, "html"));
+        if let Some(Node::Element { children, .. }) = html {
+            let head = children.iter_mut().find(|node| is_element(node, "head"));
+            if let Some(Node::Element { children, .. }) = head {
+                children.push(Node::new_element("title", vec![], vec![text]));
+            }
+        }
+    }
+
+    /// Get the `href` of the first `base` element that has one, which the
+    /// relative urls in the document are based on.
+    pub fn base_url(&self) -> Option<&str> {
+        let base = find(&self.nodes, |node| {
+            is_element(node, "base") && attr(node, "href").is_some()
+        })?;
+        attr(base, "href")
+    }
+
+    /// Get the `lang` of the `html` element.
+    pub fn lang(&self) -> Option<&str> {
+        attr(self.html_node()?, "lang")
+    }
+
+    /// Get the charset declared by the first `<meta charset>`, or by
+    /// `<meta http-equiv="Content-Type" content="text/html; charset=...">`.
+    pub fn charset(&self) -> Option<&str> {
+        let meta = find(&self.nodes, |node| {
+            is_element(node, "meta") && meta_charset(node).is_some()
+        })?;
+        meta_charset(meta)
+    }
+
+    /// Copy the borrowed names and texts like [`Node::into_owned`].
+    pub fn into_owned(self) -> Document<'static> {
+        Document {
+            nodes: self.nodes.into_iter().map(Node::into_owned).collect(),
+        }
+    }
+
+    fn html_node(&self) -> Option<&Node<'a>> {
+        self.nodes.iter().find(|node| is_element(node, "html"))
+    }
+
+    // Find the child of the `html` element that is `<name>`.
+    fn html_child(&self, name: &str) -> Option<&Node<'a>> {
+        children(self.html_node()?)
+            .iter()
+            .find(|node| is_element(node, name))
+    }
+}
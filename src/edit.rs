@@ -1,7 +1,9 @@
-use crate::{Element, Node, Selector};
+use std::mem;
+
+use crate::{query::matches, Document, Element, Node, Selector};
 
 /// Used to insert or remove elements by `Selector`, and trim the dom.
-pub trait Editable {
+pub trait Editable<'a> {
     /// Remove all empty text nodes from `self`.
     /// 
     /// ```
@@ -30,12 +32,12 @@ pub trait Editable {
     ///     .insert_to(&selector, Node::new_element(
     ///         "span",
     ///         vec![],
-    ///         vec![Node::Text("Cancel".to_string())]
+    ///         vec![Node::new_text("Cancel")]
     ///     ))
     ///     .html();
     /// assert_eq!(html, r#"<div><span>Ok</span><span>Cancel</span></div>"#)
     /// ```
-    fn insert_to(&mut self, selector: &Selector, target: Node) -> &mut Self;
+    fn insert_to(&mut self, selector: &Selector, target: Node<'a>) -> &mut Self;
     /// Remove all elements that matches the `selector`.
     /// 
     /// ```
@@ -58,49 +60,125 @@ pub trait Editable {
     /// </div>"#)
     /// ```
     fn remove_by(&mut self, selector: &Selector) -> &mut Self;
+    /// Edit the content of all `<template>` elements that matches the
+    /// `selector`, which the other methods leave out.
+    ///
+    /// The default implementation edits nothing, for the types that have
+    /// no templates in them.
+    ///
+    /// ```
+    /// use html_query_parser::{parse, Editable, Htmlifiable, Selector};
+    ///
+    /// let html = r#"<template id="row"><li>Name</li><li class="ad"></li></template>"#;
+    ///
+    /// let selector = Selector::from(".ad");
+    /// let html = parse(html)
+    ///     .edit_content(&Selector::from("#row"), |content| {
+    ///         content.remove_by(&selector);
+    ///     })
+    ///     .html();
+    /// assert_eq!(html, r#"<template id="row"><li>Name</li></template>"#)
+    /// ```
+    fn edit_content(
+        &mut self,
+        selector: &Selector,
+        edit: impl FnMut(&mut Vec<Node<'a>>),
+    ) -> &mut Self {
+        let _ = (selector, edit);
+        self
+    }
 }
 
-impl Editable for Vec<Node> {
+// Call `edit` with the content of the templates in `nodes` that matches the
+// `selector`, after that of the templates in them.
+fn edit_content<'a>(
+    nodes: &mut [Node<'a>],
+    selector: &Selector,
+    edit: &mut dyn FnMut(&mut Vec<Node<'a>>),
+) {
+    // The content of the elements being searched if it is to be edited, with
+    // their children left.
+    let mut stack = vec![(None, nodes.iter_mut())];
+    while let Some((_, nodes)) = stack.last_mut() {
+        match nodes.next() {
+            Some(Node::Element {
+                name,
+                attrs,
+                children,
+                namespace,
+                content,
+                ..
+            }) => {
+                let content = content
+                    .as_mut()
+                    .filter(|_| selector.matches_tag(name, attrs, *namespace));
+                stack.push((content, children.iter_mut()));
+            }
+            Some(_) => {}
+            None => {
+                if let Some((Some(content), _)) = stack.pop() {
+                    edit(content);
+                }
+            }
+        }
+    }
+}
+
+// The nodes are edited without recursion, like they are queried, so that
+// deeply nested nodes cannot overflow the stack.
+impl<'a> Editable<'a> for Vec<Node<'a>> {
     fn trim(self) -> Self {
-        let mut nodes: Vec<Node> = Vec::new();
-        for node in self {
-            match node {
-                Node::Element {
-                    name,
-                    attrs,
-                    children,
-                } => nodes.push(Node::Element {
-                    name,
-                    attrs,
-                    children: children.trim(),
-                }),
-                Node::Text(text) => {
-                    if text.trim() != "" {
-                        nodes.push(Node::Text(text));
+        // The elements being trimmed, with their children left and kept.
+        let mut stack = vec![(None, self.into_iter(), Vec::new())];
+        loop {
+            let (_, nodes, kept) = stack.last_mut().unwrap();
+            match nodes.next() {
+                Some(mut node) => match &mut node {
+                    Node::Element { children, .. } => {
+                        let children = mem::take(children).into_iter();
+                        stack.push((Some(node), children, Vec::new()));
+                    }
+                    Node::Text { text, .. } if text.trim().is_empty() => {}
+                    Node::Comment(..) => {}
+                    _ => kept.push(node),
+                },
+                None => {
+                    let (element, _, kept) = stack.pop().unwrap();
+                    match (element, stack.last_mut()) {
+                        (Some(mut element), Some((_, _, parent_kept))) => {
+                            if let Node::Element { children, .. } = &mut element {
+                                *children = kept;
+                            }
+                            parent_kept.push(element);
+                        }
+                        _ => return kept,
                     }
                 }
-                Node::Comment(_) => {}
-                Node::Doctype => nodes.push(node),
             }
         }
-        nodes
     }
     
-    fn insert_to(&mut self, selector: &Selector, target: Node) -> &mut Self {
-        for node in self.iter_mut() {
-            if let Node::Element {
-                name,
-                attrs,
-                children,
-            } = node
-            {
-                children.insert_to(selector, target.clone());
-                if selector.matches(&Element {
-                    name: name.clone(),
-                    attrs: attrs.clone(),
-                    children: vec![],
-                }) {
-                    children.push(target.clone());
+    fn insert_to(&mut self, selector: &Selector, target: Node<'a>) -> &mut Self {
+        let mut stack = vec![self.iter_mut()];
+        while let Some(nodes) = stack.last_mut() {
+            match nodes.next() {
+                Some(Node::Element {
+                    name,
+                    attrs,
+                    children,
+                    namespace,
+                    ..
+                }) => {
+                    // The target is not searched itself.
+                    let len = children.len();
+                    if selector.matches_tag(name, attrs, *namespace) {
+                        children.push(target.clone());
+                    }
+                    stack.push(children[..len].iter_mut());
+                }
+                Some(_) => {}
+                None => {
+                    stack.pop();
                 }
             }
         }
@@ -108,36 +186,37 @@ impl Editable for Vec<Node> {
     }
 
     fn remove_by(&mut self, selector: &Selector) -> &mut Self {
-        self.retain(|node| {
-            if let Node::Element { name, attrs, .. } = node {
-                let element = Element {
-                    name: name.clone(),
-                    attrs: attrs.clone(),
-                    children: vec![],
-                };
-                return !selector.matches(&element);
-            }
-            true
-        });
-        for node in self.iter_mut() {
-            if let Node::Element { children, .. } = node {
-                children.remove_by(selector);
+        let mut stack = vec![&mut *self];
+        while let Some(nodes) = stack.pop() {
+            nodes.retain(|node| !matches(selector, node));
+            for node in nodes {
+                if let Node::Element { children, .. } = node {
+                    stack.push(children);
+                }
             }
         }
         self
     }
+
+    fn edit_content(
+        &mut self,
+        selector: &Selector,
+        mut edit: impl FnMut(&mut Vec<Node<'a>>),
+    ) -> &mut Self {
+        edit_content(self, selector, &mut edit);
+        self
+    }
 }
 
-impl Editable for Element {
+impl<'a> Editable<'a> for Element<'a> {
     fn trim(self) -> Self {
         Element {
-            name: self.name,
-            attrs: self.attrs,
             children: self.children.trim(),
+            ..self
         }
     }
 
-    fn insert_to(&mut self, selector: &Selector, target: Node) -> &mut Self {
+    fn insert_to(&mut self, selector: &Selector, target: Node<'a>) -> &mut Self {
         self.children.insert_to(selector, target.clone());
         if selector.matches(self) {
             self.children.push(target);
@@ -149,4 +228,45 @@ impl Editable for Element {
         self.children.remove_by(selector);
         self
     }
+
+    fn edit_content(
+        &mut self,
+        selector: &Selector,
+        mut edit: impl FnMut(&mut Vec<Node<'a>>),
+    ) -> &mut Self {
+        edit_content(&mut self.children, selector, &mut edit);
+        if selector.matches(self) {
+            if let Some(content) = &mut self.content {
+                edit(content);
+            }
+        }
+        self
+    }
+}
+
+impl<'a> Editable<'a> for Document<'a> {
+    fn trim(self) -> Self {
+        Document {
+            nodes: self.nodes.trim(),
+        }
+    }
+
+    fn insert_to(&mut self, selector: &Selector, target: Node<'a>) -> &mut Self {
+        self.nodes.insert_to(selector, target);
+        self
+    }
+
+    fn remove_by(&mut self, selector: &Selector) -> &mut Self {
+        self.nodes.remove_by(selector);
+        self
+    }
+
+    fn edit_content(
+        &mut self,
+        selector: &Selector,
+        edit: impl FnMut(&mut Vec<Node<'a>>),
+    ) -> &mut Self {
+        self.nodes.edit_content(selector, edit);
+        self
+    }
 }
@@ -1,4 +1,38 @@
-use crate::{data::VOID_TAGS, Element, Node};
+use std::{borrow::Cow, collections::HashMap, ops::Range, slice};
+
+use crate::{
+    data::RCDATA_TAGS, parse::decode_entities, Document, Element, Namespace, Node, ParseErrorKind,
+    Span, Token, Tokenizer,
+};
+
+// Escape `&`, `<`, `>` and no-break spaces in text.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\u{A0}' => escaped.push_str("&nbsp;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// Escape `&`, `"` and no-break spaces in attribute values.
+fn escape_attr(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\u{A0}' => escaped.push_str("&nbsp;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
 
 /// Used to be converted to html string
 pub trait Htmlifiable {
@@ -11,7 +45,7 @@ pub trait Htmlifiable {
     ///     "span",
     ///     vec![("class", "info")],
     ///     vec![
-    ///         Node::Text("Hello World!".to_string())
+    ///         Node::new_text("Hello World!")
     ///     ]
     /// );
     /// assert_eq!(node.html(), r#"<span class="info">Hello World!</span>"#);
@@ -23,61 +57,661 @@ pub trait Htmlifiable {
     /// assert_eq!(element.html(), r#"<span class="info">Hello World!</span>"#);
     /// ```
     fn html(&self) -> String;
+    /// Convert the object to html string like [`html`](Htmlifiable::html),
+    /// but write the nodes that are not edited byte-for-byte as they are in
+    /// the `source` that they are parsed from, with their quotes, attribute
+    /// order, whitespace and case, so that editing one element does not
+    /// change the rest of the html.
+    ///
+    /// The nodes are found in `source` by their [`Span`]s, so they have to
+    /// be parsed with [`ParseOptions::spans`](crate::ParseOptions::spans)
+    /// enabled. The nodes without spans, like the new ones, and those that
+    /// do not match their source any longer are converted like `html()`,
+    /// while the nodes inside them are still written as they are. So are
+    /// the attributes of an edited element that are not edited, in their
+    /// order in the source and followed by the new ones. What is not in the
+    /// nodes, like the stray end tags dropped by the parser, is not kept.
+    ///
+    /// The default implementation ignores `source` and calls `html()`.
+    ///
+    /// ```
+    /// use html_query_parser::{parse_with, Htmlifiable, Node, ParseOptions};
+    ///
+    /// let options = ParseOptions {
+    ///     spans: true,
+    ///     ..ParseOptions::default()
+    /// };
+    /// let html = "<!doctype html>\n<UL class='list' id=a>\n  <li>One<br/>\n  <li>Two\n</UL>";
+    /// let mut nodes = parse_with(html, &options);
+    /// assert_eq!(nodes.lossless_html(html), html);
+    ///
+    /// if let Node::Element { attrs, .. } = &mut nodes[2] {
+    ///     attrs.remove("id");
+    /// }
+    /// assert_eq!(
+    ///     nodes.lossless_html(html),
+    ///     "<!doctype html>\n<UL class='list'>\n  <li>One<br/>\n  <li>Two\n</UL>"
+    /// );
+    /// ```
+    fn lossless_html(&self, source: &str) -> String {
+        let _ = source;
+        self.html()
+    }
 }
 
-impl Htmlifiable for Element {
-    fn html(&self) -> String {
-        if self.attrs.len() == 0 {
-            return if VOID_TAGS.contains(&self.name.as_str()) {
-                format!("<{}>", self.name)
-            } else {
-                format!("<{}>{}</{}>", self.name, self.children.html(), self.name)
-            };
-        }
-        let attrs = self
-            .attrs
-            .iter()
-            .map(|(k, v)| {
-                format!(
-                    "{}=\"{}\"",
-                    k,
-                    v.replace("\"", "\\\"").replace("\'", "\\\'")
-                )
-            })
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        if VOID_TAGS.contains(&self.name.as_str()) {
-            format!("<{} {}>", self.name, attrs,)
-        } else {
-            format!(
-                "<{} {}>{}</{}>",
-                self.name,
+// Quote the doctype identifier with `"`, or `'` if it contains `"`.
+fn quote(id: &str) -> String {
+    if id.contains('"') {
+        format!("'{}'", id)
+    } else {
+        format!("\"{}\"", id)
+    }
+}
+
+// Push the attribute like ` class="app"` to `html`. The `raw` value, whose
+// character references are not decoded, is not escaped but quoted with `'`
+// if it has `"`.
+fn push_attr(html: &mut String, key: &str, value: &str, raw: bool) {
+    html.push(' ');
+    html.push_str(key);
+    html.push('=');
+    if !raw {
+        html.push('"');
+        html.push_str(&escape_attr(value));
+        html.push('"');
+    } else if value.contains('\'') {
+        html.push_str(&quote(&value.replace('"', "&quot;")));
+    } else {
+        html.push_str(&quote(value));
+    }
+}
+
+// What the start tag of an element is written from, with how the nodes in
+// it are written.
+#[derive(Clone, Copy)]
+struct StartTag<'n, 'a> {
+    name: &'n str,
+    attrs: &'n HashMap<Cow<'a, str>, Cow<'a, str>>,
+    attr_spans: &'n HashMap<Cow<'a, str>, Span>,
+    namespace: Namespace,
+    raw_attrs: bool,
+    void: bool,
+    raw_text: bool,
+}
+
+impl<'n, 'a> StartTag<'n, 'a> {
+    fn of(element: &'n Element<'a>) -> Self {
+        StartTag {
+            name: &element.name,
+            attrs: &element.attrs,
+            attr_spans: &element.attr_spans,
+            namespace: element.namespace,
+            raw_attrs: element.raw_attrs,
+            void: element.void,
+            raw_text: element.raw_text,
+        }
+    }
+
+    fn is_rcdata(&self) -> bool {
+        self.namespace == Namespace::Html
+            && RCDATA_TAGS
+                .iter()
+                .any(|tag| tag.eq_ignore_ascii_case(self.name))
+    }
+}
+
+// Push the start tag like `<div class="app">` to `html`, and return whether
+// the element needs an end tag.
+fn push_start_tag(html: &mut String, tag: &StartTag<'_, '_>, is_empty: bool) -> bool {
+    html.push('<');
+    html.push_str(tag.name);
+    for (key, value) in tag.attrs {
+        push_attr(html, key, value, tag.raw_attrs);
+    }
+    end_start_tag(html, tag, is_empty)
+}
+
+// Push the end of the start tag after its attributes, and return whether
+// the element needs an end tag. Void elements have none, and svg and MathML
+// elements without children are self-closing like `<path/>`.
+fn end_start_tag(html: &mut String, tag: &StartTag<'_, '_>, is_empty: bool) -> bool {
+    if tag.namespace != Namespace::Html && is_empty {
+        html.push_str("/>");
+        return false;
+    }
+    html.push('>');
+    !tag.void
+}
+
+// The nodes inside an element, which are the content of `<template>`
+// followed by the children.
+fn inner_nodes<'n, 'a>(
+    children: &'n [Node<'a>],
+    content: &'n Option<Vec<Node<'a>>>,
+) -> impl Iterator<Item = &'n Node<'a>> + Clone {
+    content.iter().flatten().chain(children)
+}
+
+// Convert the nodes to html without recursion, so that deeply nested nodes
+// cannot overflow the stack. The text is not escaped if `raw_text`, like
+// in `script`.
+fn nodes_html(nodes: &[Node<'_>], raw_text: bool) -> String {
+    let mut html = String::new();
+    // The elements being converted, with whether their text is raw and the
+    // nodes left inside them.
+    let mut stack = vec![(None, raw_text, inner_nodes(nodes, &None))];
+    while let Some((parent, raw_text, children)) = stack.last_mut() {
+        match children.next() {
+            Some(Node::Element {
+                name,
                 attrs,
-                self.children.html(),
-                self.name
-            )
+                children,
+                attr_spans,
+                namespace,
+                content,
+                raw_attrs,
+                void,
+                raw_text,
+                ..
+            }) => {
+                let tag = StartTag {
+                    name,
+                    attrs,
+                    attr_spans,
+                    namespace: *namespace,
+                    raw_attrs: *raw_attrs,
+                    void: *void,
+                    raw_text: *raw_text,
+                };
+                if push_start_tag(&mut html, &tag, children.is_empty()) {
+                    let nodes = inner_nodes(children, content);
+                    stack.push((Some(name), tag.raw_text, nodes));
+                }
+            }
+            Some(Node::Text { text, .. }) if *raw_text => html.push_str(text),
+            Some(node) => html.push_str(&node.html()),
+            None => {
+                if let Some(name) = parent {
+                    html.push_str("</");
+                    html.push_str(name);
+                    html.push('>');
+                }
+                stack.pop();
+            }
         }
     }
+    html
 }
 
-impl Htmlifiable for Node {
-    fn html(&self) -> String {
-        match self {
-            Node::Element { .. } => self.clone().try_into_element().unwrap().html(),
-            Node::Text(text) => text.to_string(),
-            Node::Comment(comment) => format!("<!--{}-->", comment),
-            Node::Doctype => "<!DOCTYPE html>".to_string(),
+// Find where the end tag of the element `<name>` is at the end of `text`,
+// like `</div >`.
+fn end_tag_in(text: &str, name: &str) -> Option<usize> {
+    let start = text.rfind("</")?;
+    let tag = &text[start + 2..];
+    let rest = tag.get(name.len()..)?;
+    let is_same = tag[..name.len()].eq_ignore_ascii_case(name)
+        && rest
+            .strip_suffix('>')?
+            .bytes()
+            .all(|byte| byte.is_ascii_whitespace());
+    is_same.then_some(start)
+}
+
+// How the end tag of an element is written by `LosslessWriter`.
+enum EndTag<'n> {
+    // As it is at the range of the source.
+    Source(Range<usize>),
+    // Omitted like in the source, like that of `li`, with the name of the
+    // element and where it ends in the source.
+    Omitted(&'n str, usize),
+    // Converted from the name of the element.
+    Name(&'n str),
+}
+
+// Whether the text ends with what may be continued into a character
+// reference, like `&l` or `&#3`.
+fn ends_in_reference(text: &str) -> bool {
+    text.rfind('&').is_some_and(|i| {
+        text[i + 1..]
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'#')
+    })
+}
+
+// Writer of `lossless_html`, which keeps track of the end tags omitted in
+// the source, since they have to be written if what is written after them
+// does not follow them in the source, like a new node. Likewise, the text
+// written as it is in the source is escaped again if it ends in a character
+// reference that what is written after it would continue, like `&` before
+// `lt` after an end tag dropped by the parser.
+struct LosslessWriter<'s, 'n> {
+    source: &'s str,
+    html: String,
+    omitted: Vec<(&'n str, usize)>,
+    // Where what was written last ends in the source, if it is from there.
+    end: Option<usize>,
+    // Where the text ending in a character reference starts in `html`, and
+    // the text.
+    reference: Option<(usize, &'n str)>,
+    tokenizer: Tokenizer<'s>,
+}
+
+impl<'s, 'n> LosslessWriter<'s, 'n> {
+    fn new(source: &'s str) -> Self {
+        LosslessWriter {
+            source,
+            html: String::new(),
+            omitted: Vec::new(),
+            end: None,
+            reference: None,
+            tokenizer: Tokenizer::new(""),
+        }
+    }
+
+    // Get the first token of `html`, like `Tokenizer::new(html).next()`.
+    fn first_token(&mut self, html: &'s str) -> Option<(Token<'s>, Span)> {
+        self.tokenizer.restart(html);
+        self.tokenizer.next()
+    }
+
+    // Get the node as it is in the source, if it is not an element and is
+    // not edited. The text is checked to have the same meaning in the
+    // element `parent`, like when `<` in `script` is moved to a `div`.
+    fn source_of(&mut self, node: &Node<'_>, parent: Option<StartTag>) -> Option<&'s str> {
+        let raw = self.source.get(node.span()?.range())?;
+        let is_same = match node {
+            Node::Element { .. } => false,
+            Node::Text {
+                text, raw: true, ..
+            } => raw == text,
+            Node::Text { text, .. } if parent.is_some_and(|tag| tag.raw_text) => raw == text,
+            // `<` at the end may start a tag with what is written after it.
+            Node::Text { .. } if raw.ends_with('<') => false,
+            Node::Text { text, .. }
+                if parent.is_some_and(|tag| tag.is_rcdata()) || !raw.contains('<') =>
+            {
+                decode_entities(raw, false, &mut Vec::new()) == *text
+            }
+            Node::Text { text, .. } => {
+                // The text may be split into tokens at `<`.
+                self.tokenizer.restart(raw);
+                let mut source_text = String::new();
+                self.tokenizer.all(|(token, _)| match token {
+                    Token::Text(text) => {
+                        source_text.push_str(&text);
+                        true
+                    }
+                    _ => false,
+                }) && source_text == *text
+            }
+            _ => match self.first_token(raw) {
+                Some((token, span)) => {
+                    // A comment not closed in the source would take what
+                    // follows it, like the nodes added after it.
+                    let errors = self.tokenizer.take_errors();
+                    let is_closed = raw.ends_with('>')
+                        && errors
+                            .iter()
+                            .all(|err| err.kind != ParseErrorKind::UnclosedComment);
+                    is_closed
+                        && span.end == raw.len()
+                        && token.into_node(None).html() == node.html()
+                }
+                None => false,
+            },
+        };
+        is_same.then_some(raw)
+    }
+
+    // Find where the start tag at `start` in the source ends, and whether it
+    // is self-closing, if it is still the start tag of the element.
+    fn source_start_tag(
+        &mut self,
+        start: usize,
+        name: &str,
+        attrs: &HashMap<Cow<'_, str>, Cow<'_, str>>,
+    ) -> Option<(usize, bool)> {
+        let (token, span) = self.first_token(self.source.get(start..)?)?;
+        let Token::StartTag {
+            name: source_name,
+            attrs: source_attrs,
+            self_closing,
+            ..
+        } = token
+        else {
+            return None;
+        };
+        // The names may have been lowercased or had their case corrected.
+        let same_attr = |key: &str, value: &str| match source_attrs.iter().find(|(k, _)| k == key) {
+            Some((_, source_value)) => source_value == value,
+            None => source_attrs
+                .iter()
+                .any(|(k, v)| k.eq_ignore_ascii_case(key) && v == value),
+        };
+        let same_attrs = source_attrs.len() == attrs.len()
+            && attrs.iter().all(|(key, value)| same_attr(key, value));
+        let is_same = source_name.eq_ignore_ascii_case(name) && same_attrs;
+        is_same.then_some((start + span.end, self_closing))
+    }
+
+    // Push the attributes of the edited element at `span` in the source to
+    // `html`, in their order in the source followed by the new ones by name.
+    // Those not edited are written as they are in the source.
+    fn push_attrs(&mut self, html: &mut String, tag: &StartTag<'_, '_>, span: Option<Span>) {
+        let source = self.source;
+        let start_tag = span.and_then(|span| self.first_token(source.get(span.start..)?));
+        let source_attrs = match start_tag {
+            Some((Token::StartTag { attrs, .. }, _)) => attrs,
+            _ => Vec::new(),
+        };
+        let mut attrs: Vec<_> = tag
+            .attrs
+            .iter()
+            .map(|(key, value)| (tag.attr_spans.get(key), key, value))
+            .collect();
+        attrs.sort_by_key(|&(span, key, _)| (span.map_or(usize::MAX, |span| span.start), key));
+        for (span, key, value) in attrs {
+            // The names may have been lowercased like in `source_start_tag`.
+            let raw = span.and_then(|span| source.get(span.range()));
+            let is_same = raw.is_some_and(|raw| {
+                raw.get(..key.len())
+                    .is_some_and(|name| name.eq_ignore_ascii_case(key))
+                    && source_attrs
+                        .iter()
+                        .any(|(k, v)| k.eq_ignore_ascii_case(key) && v == value)
+            });
+            match raw {
+                Some(raw) if is_same => {
+                    html.push(' ');
+                    html.push_str(raw);
+                }
+                _ => push_attr(html, key, value, tag.raw_attrs),
+            }
+        }
+    }
+
+    // Push `text`, which starts at `start` if it is from the source.
+    fn push(&mut self, text: &str, start: Option<usize>) {
+        if text.is_empty() {
+            return;
+        }
+        let len = self.html.len();
+        for (name, end) in self.omitted.drain(..) {
+            if start != Some(end) {
+                self.html.push_str("</");
+                self.html.push_str(name);
+                self.html.push('>');
+            }
+        }
+        let continues =
+            text.starts_with(|ch: char| ch.is_ascii_alphanumeric() || "#;".contains(ch));
+        if let Some((at, reference)) = self.reference.take() {
+            if continues && start != self.end && self.html.len() == len {
+                self.html.truncate(at);
+                self.html.push_str(&escape_text(reference));
+            }
+        }
+        self.html.push_str(text);
+        self.end = start.map(|start| start + text.len());
+    }
+
+    // Push the nodes in the element `parent`, if any, like `nodes_html`
+    // without recursion.
+    fn push_nodes<'a: 'n>(&mut self, nodes: &'n [Node<'a>], parent: Option<StartTag<'n, 'a>>) {
+        // The end tags and start tags of the elements being written, with
+        // the nodes left inside them.
+        let mut stack = vec![(None, parent, inner_nodes(nodes, &None))];
+        while let Some((_, parent, children)) = stack.last_mut() {
+            match children.next() {
+                Some(Node::Element {
+                    name,
+                    attrs,
+                    children,
+                    span,
+                    attr_spans,
+                    namespace,
+                    content,
+                    raw_attrs,
+                    void,
+                    raw_text,
+                }) => {
+                    let tag = StartTag {
+                        name,
+                        attrs,
+                        attr_spans,
+                        namespace: *namespace,
+                        raw_attrs: *raw_attrs,
+                        void: *void,
+                        raw_text: *raw_text,
+                    };
+                    let nodes = inner_nodes(children, content);
+                    let end_tag = self.push_start_tag(tag, *span, nodes.clone());
+                    if let Some(end_tag) = end_tag {
+                        stack.push((Some(end_tag), Some(tag), nodes));
+                    }
+                }
+                Some(node) => {
+                    let parent = *parent;
+                    match self.source_of(node, parent) {
+                        Some(raw) => {
+                            self.push(raw, node.span().map(|span| span.start));
+                            match node {
+                                Node::Text {
+                                    text, raw: false, ..
+                                } if !parent.is_some_and(|tag| tag.raw_text)
+                                    && ends_in_reference(raw) =>
+                                {
+                                    self.reference = Some((self.html.len() - raw.len(), text));
+                                }
+                                _ => {}
+                            }
+                        }
+                        None => match node {
+                            Node::Text { text, .. } if parent.is_some_and(|tag| tag.raw_text) => {
+                                self.push(text, None)
+                            }
+                            node => self.push(&node.html(), None),
+                        },
+                    }
+                }
+                None => {
+                    if let Some((Some(end_tag), ..)) = stack.pop() {
+                        self.push_end_tag(end_tag);
+                    }
+                }
+            }
+        }
+    }
+
+    // Push the start tag of the element, as it is in the source if it is
+    // not edited, and return how to write its end tag, or `None` if it has
+    // none and the nodes inside it are not written, like in void elements.
+    fn push_start_tag<'a: 'n>(
+        &mut self,
+        tag: StartTag<'n, 'a>,
+        span: Option<Span>,
+        inner: impl Iterator<Item = &'n Node<'a>> + Clone,
+    ) -> Option<EndTag<'n>> {
+        let StartTag { name, attrs, .. } = tag;
+        let source = self.source;
+        let is_empty = inner.clone().next().is_none();
+        let span = span.filter(|span| span.start.max(span.end) <= source.len());
+        let start_tag = span.and_then(|span| self.source_start_tag(span.start, name, attrs));
+        match (span, start_tag) {
+            // A self-closing element has no content in the source.
+            (Some(span), Some((end, self_closing))) if is_empty || !self_closing => {
+                self.push(&source[span.start..end], Some(span.start));
+                if self_closing || tag.void {
+                    return None;
+                }
+                Some(self.source_end_tag(name, span, end, inner, true))
+            }
+            // An implied element, like `tbody`, has no start tag in the
+            // source, and starts with the first node in it.
+            (Some(span), None)
+                if attrs.is_empty()
+                    && inner
+                        .clone()
+                        .find_map(Node::span)
+                        .map_or(span.end <= span.start, |first| first.start == span.start) =>
+            {
+                Some(self.source_end_tag(name, span, span.start, inner, true))
+            }
+            // The end tag in the source is still written if the name is not
+            // changed.
+            (span, _) => {
+                let mut start_tag = String::from("<");
+                start_tag.push_str(name);
+                self.push_attrs(&mut start_tag, &tag, span);
+                let has_end_tag = end_start_tag(&mut start_tag, &tag, is_empty);
+                self.push(&start_tag, None);
+                has_end_tag.then(|| match span {
+                    Some(span) => self.source_end_tag(name, span, span.start, inner, false),
+                    None => EndTag::Name(name),
+                })
+            }
+        }
+    }
+
+    // Find the end tag of the element at `span` in the source, whose start
+    // tag ends at `start_end`, where it is omitted if it is not there and
+    // `can_omit`.
+    fn source_end_tag<'a: 'n>(
+        &self,
+        name: &'n str,
+        span: Span,
+        start_end: usize,
+        inner: impl Iterator<Item = &'n Node<'a>>,
+        can_omit: bool,
+    ) -> EndTag<'n> {
+        let content_end = inner
+            .filter_map(Node::span)
+            .fold(start_end, |end, span| end.max(span.end));
+        let rest = self.source.get(content_end..span.end).unwrap_or("");
+        match end_tag_in(rest, name) {
+            Some(i) => EndTag::Source(content_end + i..span.end),
+            None if can_omit && (rest.is_empty() || start_end == span.start) => {
+                EndTag::Omitted(name, span.end)
+            }
+            None => EndTag::Name(name),
+        }
+    }
+
+    fn push_end_tag(&mut self, end_tag: EndTag<'n>) {
+        match end_tag {
+            EndTag::Source(range) => {
+                let start = range.start;
+                self.push(&self.source[range], Some(start));
+            }
+            EndTag::Omitted(name, end) => self.omitted.push((name, end)),
+            EndTag::Name(name) => self.push(&format!("</{}>", name), None),
         }
     }
 }
 
-impl Htmlifiable for Vec<Node> {
+impl Htmlifiable for Element<'_> {
     fn html(&self) -> String {
         let mut html = String::new();
-        for node in self {
-            html.push_str(node.html().as_str());
+        let is_empty = self.children.is_empty();
+        if push_start_tag(&mut html, &StartTag::of(self), is_empty) {
+            if let Some(content) = &self.content {
+                html.push_str(&nodes_html(content, self.raw_text));
+            }
+            html.push_str(&nodes_html(&self.children, self.raw_text));
+            html.push_str("</");
+            html.push_str(&self.name);
+            html.push('>');
         }
         html
     }
+
+    fn lossless_html(&self, source: &str) -> String {
+        let mut writer = LosslessWriter::new(source);
+        let nodes = inner_nodes(&self.children, &self.content);
+        let tag = StartTag::of(self);
+        let end_tag = writer.push_start_tag(tag, self.span, nodes);
+        if let Some(end_tag) = end_tag {
+            if let Some(content) = &self.content {
+                writer.push_nodes(content, Some(tag));
+            }
+            writer.push_nodes(&self.children, Some(tag));
+            writer.push_end_tag(end_tag);
+        }
+        writer.html
+    }
+}
+
+impl Htmlifiable for Node<'_> {
+    fn html(&self) -> String {
+        match self {
+            Node::Element { .. } => nodes_html(slice::from_ref(self), false),
+            Node::Text {
+                text, raw: true, ..
+            } => text.to_string(),
+            Node::Text { text, .. } => escape_text(text),
+            Node::Comment(comment, _) => format!("<!--{}-->", comment),
+            Node::CData(text, _) => format!("<![CDATA[{}]]>", text),
+            Node::ProcessingInstruction { target, data, .. } => {
+                if data.is_empty() {
+                    format!("<?{}?>", target)
+                } else {
+                    format!("<?{} {}?>", target, data)
+                }
+            }
+            Node::Doctype {
+                name,
+                public_id,
+                system_id,
+                ..
+            } => {
+                let mut html = String::from("<!DOCTYPE");
+                if let Some(name) = name {
+                    html.push(' ');
+                    html.push_str(name);
+                }
+                match (public_id, system_id) {
+                    (Some(public_id), _) => {
+                        html.push_str(" PUBLIC ");
+                        html.push_str(&quote(public_id));
+                    }
+                    (None, Some(_)) => html.push_str(" SYSTEM"),
+                    (None, None) => {}
+                }
+                if let Some(system_id) = system_id {
+                    html.push(' ');
+                    html.push_str(&quote(system_id));
+                }
+                html.push('>');
+                html
+            }
+        }
+    }
+
+    fn lossless_html(&self, source: &str) -> String {
+        let mut writer = LosslessWriter::new(source);
+        writer.push_nodes(slice::from_ref(self), None);
+        writer.html
+    }
+}
+
+impl Htmlifiable for Vec<Node<'_>> {
+    fn html(&self) -> String {
+        nodes_html(self, false)
+    }
+
+    fn lossless_html(&self, source: &str) -> String {
+        let mut writer = LosslessWriter::new(source);
+        writer.push_nodes(self, None);
+        writer.html
+    }
+}
+
+impl Htmlifiable for Document<'_> {
+    fn html(&self) -> String {
+        self.nodes.html()
+    }
+
+    fn lossless_html(&self, source: &str) -> String {
+        self.nodes.lossless_html(source)
+    }
 }
@@ -1,79 +1,383 @@
+mod document;
 mod edit;
 mod html;
+mod namespace;
 mod parse;
 mod query;
+mod quirks;
 mod data;
 
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap};
 
+use data::{RAW_TEXT_TAGS, VOID_TAGS};
+
+pub use document::Document;
 pub use edit::Editable;
 pub use html::Htmlifiable;
-pub use parse::parse;
+pub use namespace::Namespace;
+pub use parse::{
+    parse, parse_bytes, parse_document, parse_fragment, parse_reader, parse_with,
+    parse_with_errors, try_parse, try_parse_with, Encoding, LimitPolicy, Limits, ParseError,
+    ParseErrorKind, ParseOptions, Parser, Span, Token, Tokenizer,
+};
 pub use query::{Queryable, Selector};
+pub use quirks::QuirksMode;
 
 /// Basic node of dom
+///
+/// The names and texts are borrowed from the parsed html, unless they have
+/// to be changed, like when character references are decoded. Call
+/// [`into_owned`](Node::into_owned) to keep the nodes after the html is
+/// dropped.
+///
+/// The [`Span`]s of the nodes and attributes are only recorded if enabled
+/// by [`ParseOptions::spans`], and are `None` or empty otherwise.
 #[derive(Debug, Clone)]
-pub enum Node {
+pub enum Node<'a> {
     Element {
-        name: String,
-        attrs: HashMap<String, String>,
-        children: Vec<Node>,
+        name: Cow<'a, str>,
+        attrs: HashMap<Cow<'a, str>, Cow<'a, str>>,
+        children: Vec<Node<'a>>,
+        span: Option<Span>,
+        attr_spans: HashMap<Cow<'a, str>, Span>,
+        namespace: Namespace,
+        content: Option<Vec<Node<'a>>>,
+        raw_attrs: bool,
+        void: bool,
+        raw_text: bool,
+    },
+    /// Text, which is written as it is by
+    /// [`html`](crate::Htmlifiable::html) if `raw` is true, like when its
+    /// character references are not decoded by
+    /// [`ParseOptions::decode_entities`].
+    Text {
+        text: Cow<'a, str>,
+        span: Option<Span>,
+        raw: bool,
+    },
+    Comment(Cow<'a, str>, Option<Span>),
+    /// Like `<![CDATA[x < y]]>`, which is common in inline svg and math.
+    CData(Cow<'a, str>, Option<Span>),
+    /// Like `<?xml version="1.0"?>`, where `xml` is the target and
+    /// `version="1.0"` is the data.
+    ProcessingInstruction {
+        target: Cow<'a, str>,
+        data: Cow<'a, str>,
+        span: Option<Span>,
     },
-    Text(String),
-    Comment(String),
-    Doctype,
+    /// Like `<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Strict//EN" "xhtml1-strict.dtd">`,
+    /// where the name is in lowercase.
+    Doctype {
+        name: Option<Cow<'a, str>>,
+        public_id: Option<Cow<'a, str>>,
+        system_id: Option<Cow<'a, str>>,
+        span: Option<Span>,
+    },
+}
+
+// Check if the html element `<name>` is void by default, like `br`.
+pub(crate) fn is_void(name: &str) -> bool {
+    VOID_TAGS.iter().any(|tag| tag.eq_ignore_ascii_case(name))
+}
+
+// Check if the text in the html element `<name>` is raw by default, like in
+// `script`.
+pub(crate) fn is_raw_text(name: &str) -> bool {
+    RAW_TEXT_TAGS
+        .iter()
+        .any(|tag| tag.eq_ignore_ascii_case(name))
+}
+
+// Copy the borrowed `text`.
+fn owned(text: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(text.into_owned())
 }
 
-impl Node {
+// Copy the borrowed keys and values of the attributes.
+fn owned_attrs(
+    attrs: HashMap<Cow<str>, Cow<str>>,
+) -> HashMap<Cow<'static, str>, Cow<'static, str>> {
+    attrs
+        .into_iter()
+        .map(|(key, value)| (owned(key), owned(value)))
+        .collect()
+}
+
+// Copy the borrowed keys of the spans of attributes.
+fn owned_keys(attr_spans: HashMap<Cow<str>, Span>) -> HashMap<Cow<'static, str>, Span> {
+    attr_spans
+        .into_iter()
+        .map(|(key, span)| (owned(key), span))
+        .collect()
+}
+
+fn owned_nodes(nodes: Vec<Node>) -> Vec<Node<'static>> {
+    nodes.into_iter().map(Node::into_owned).collect()
+}
+
+impl<'a> Node<'a> {
     /// Check if it is an element node.
     pub fn is_element(&self) -> bool {
+        matches!(self, Node::Element { .. })
+    }
+
+    /// Get the quirks mode that the doctype node sets, or `None` if it is
+    /// not a doctype. Documents without a doctype are in the quirks mode.
+    ///
+    /// ```
+    /// use html_query_parser::{parse, QuirksMode};
+    ///
+    /// let nodes = parse(r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN"
+    ///     "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd">"#);
+    /// assert_eq!(nodes[0].quirks_mode(), Some(QuirksMode::LimitedQuirks));
+    /// ```
+    pub fn quirks_mode(&self) -> Option<QuirksMode> {
+        match self {
+            Node::Doctype {
+                name,
+                public_id,
+                system_id,
+                ..
+            } => Some(QuirksMode::from_doctype(
+                name.as_deref(),
+                public_id.as_deref(),
+                system_id.as_deref(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Get where the node is in the source html, if recorded.
+    ///
+    /// ```
+    /// use html_query_parser::{parse_with, ParseOptions};
+    ///
+    /// let options = ParseOptions {
+    ///     spans: true,
+    ///     ..ParseOptions::default()
+    /// };
+    /// let html = "<ul>\n  <li>One</li>\n</ul>";
+    /// let nodes = parse_with(html, &options);
+    /// let ul = nodes[0].clone().try_into_element().unwrap();
+    /// let span = ul.children[1].span().unwrap();
+    /// assert_eq!(&html[span.range()], "<li>One</li>");
+    /// assert_eq!((span.line, span.column), (2, 3));
+    /// ```
+    pub fn span(&self) -> Option<Span> {
         match self {
-            Node::Element { .. } => true,
-            _ => false,
+            Node::Element { span, .. }
+            | Node::Text { span, .. }
+            | Node::Comment(_, span)
+            | Node::CData(_, span)
+            | Node::ProcessingInstruction { span, .. }
+            | Node::Doctype { span, .. } => *span,
+        }
+    }
+
+    /// Create a new text node.
+    pub fn new_text(text: &str) -> Self {
+        Node::Text {
+            text: Cow::Owned(text.to_string()),
+            span: None,
+            raw: false,
+        }
+    }
+
+    /// Copy the borrowed names and texts, so that the node can outlive the
+    /// html that it is parsed from.
+    ///
+    /// ```
+    /// use html_query_parser::{parse, Htmlifiable, Node};
+    ///
+    /// fn parse_owned(html: String) -> Vec<Node<'static>> {
+    ///     parse(&html).into_iter().map(Node::into_owned).collect()
+    /// }
+    ///
+    /// let nodes = parse_owned(String::from("<p>Hello</p>"));
+    /// assert_eq!(nodes.html(), "<p>Hello</p>");
+    /// ```
+    pub fn into_owned(self) -> Node<'static> {
+        match self {
+            Node::Element {
+                name,
+                attrs,
+                children,
+                span,
+                attr_spans,
+                namespace,
+                content,
+                raw_attrs,
+                void,
+                raw_text,
+            } => Node::Element {
+                name: owned(name),
+                attrs: owned_attrs(attrs),
+                children: owned_nodes(children),
+                span,
+                attr_spans: owned_keys(attr_spans),
+                namespace,
+                content: content.map(owned_nodes),
+                raw_attrs,
+                void,
+                raw_text,
+            },
+            Node::Text { text, span, raw } => Node::Text {
+                text: owned(text),
+                span,
+                raw,
+            },
+            Node::Comment(comment, span) => Node::Comment(owned(comment), span),
+            Node::CData(text, span) => Node::CData(owned(text), span),
+            Node::ProcessingInstruction { target, data, span } => Node::ProcessingInstruction {
+                target: owned(target),
+                data: owned(data),
+                span,
+            },
+            Node::Doctype {
+                name,
+                public_id,
+                system_id,
+                span,
+            } => Node::Doctype {
+                name: name.map(owned),
+                public_id: public_id.map(owned),
+                system_id: system_id.map(owned),
+                span,
+            },
         }
     }
 
     /// Try to convert the node into an element.
-    pub fn try_into_element(self) -> Result<Element, &'static str> {
+    pub fn try_into_element(self) -> Result<Element<'a>, &'static str> {
         match self {
             Node::Element {
                 name,
                 attrs,
                 children,
+                span,
+                attr_spans,
+                namespace,
+                content,
+                raw_attrs,
+                void,
+                raw_text,
             } => Ok(Element {
                 name,
                 attrs,
                 children,
+                span,
+                attr_spans,
+                namespace,
+                content,
+                raw_attrs,
+                void,
+                raw_text,
             }),
             _ => Err("not an element"),
         }
     }
 
     /// Create a new element node.
-    pub fn new_element(name: &str, attrs: Vec<(&str, &str)>, children: Vec<Node>) -> Node {
+    pub fn new_element(name: &str, attrs: Vec<(&str, &str)>, children: Vec<Node<'a>>) -> Self {
         Node::Element {
-            name: name.to_string(),
-            attrs: attrs.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            name: Cow::Owned(name.to_string()),
+            attrs: attrs
+                .into_iter()
+                .map(|(k, v)| (Cow::Owned(k.to_string()), Cow::Owned(v.to_string())))
+                .collect(),
             children,
+            span: None,
+            attr_spans: HashMap::new(),
+            namespace: Namespace::Html,
+            content: None,
+            raw_attrs: false,
+            void: is_void(name),
+            raw_text: is_raw_text(name),
         }
     }
 }
 
 /// HTML Element
 #[derive(Debug)]
-pub struct Element {
-    pub name: String,
-    pub attrs: HashMap<String, String>,
-    pub children: Vec<Node>,
+pub struct Element<'a> {
+    pub name: Cow<'a, str>,
+    pub attrs: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    pub children: Vec<Node<'a>>,
+    /// Where the element is in the source html, from the start tag to the
+    /// end tag, if recorded.
+    pub span: Option<Span>,
+    /// Where the attributes are in the source html, like `class="app"`, if
+    /// recorded.
+    pub attr_spans: HashMap<Cow<'a, str>, Span>,
+    /// Svg or MathML inside `<svg>` or `<math>`, and html otherwise.
+    pub namespace: Namespace,
+    /// The content of `<template>`, which is a separate fragment rather
+    /// than its children, or `None` for other elements.
+    ///
+    /// It is inert like in browsers, so it is left out of
+    /// [`query`](Queryable::query), [`insert_to`](Editable::insert_to),
+    /// etc., and can be queried by itself or edited by
+    /// [`edit_content`](Editable::edit_content).
+    ///
+    /// ```
+    /// use html_query_parser::{parse, Queryable, Selector};
+    ///
+    /// let nodes = parse(r#"<template><p class="row">Row</p></template>"#);
+    /// let selector = Selector::from(".row");
+    /// assert!(nodes.query(&selector).is_none());
+    ///
+    /// let template = nodes.query(&Selector::from("template")).unwrap();
+    /// let content = template.content.unwrap();
+    /// assert!(content.query(&selector).is_some());
+    /// ```
+    pub content: Option<Vec<Node<'a>>>,
+    /// Whether the attribute values are kept as they are in the source,
+    /// with their character references not decoded by
+    /// [`ParseOptions::decode_entities`], so that
+    /// [`html`](crate::Htmlifiable::html) does not escape them again.
+    pub raw_attrs: bool,
+    /// Whether the element is void like `br`, which has no content or end
+    /// tag, as [`ParseOptions::void_tags`] are.
+    pub void: bool,
+    /// Whether the text in the element is raw like in `script`, which is
+    /// not escaped by [`html`](crate::Htmlifiable::html), as in
+    /// [`ParseOptions::raw_text_tags`].
+    pub raw_text: bool,
 }
 
-impl Element {
+impl<'a> Element<'a> {
     /// Create a new element.
-    pub fn new(name: &str, attrs: Vec<(&str, &str)>, children: Vec<Node>) -> Self {
+    pub fn new(name: &str, attrs: Vec<(&str, &str)>, children: Vec<Node<'a>>) -> Self {
         Self {
-            name: name.to_string(),
-            attrs: attrs.into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            name: Cow::Owned(name.to_string()),
+            attrs: attrs
+                .into_iter()
+                .map(|(k, v)| (Cow::Owned(k.to_string()), Cow::Owned(v.to_string())))
+                .collect(),
             children,
+            span: None,
+            attr_spans: HashMap::new(),
+            namespace: Namespace::Html,
+            content: None,
+            raw_attrs: false,
+            void: is_void(name),
+            raw_text: is_raw_text(name),
+        }
+    }
+
+    /// Copy the borrowed names and texts like [`Node::into_owned`].
+    pub fn into_owned(self) -> Element<'static> {
+        Element {
+            name: owned(self.name),
+            attrs: owned_attrs(self.attrs),
+            children: owned_nodes(self.children),
+            span: self.span,
+            attr_spans: owned_keys(self.attr_spans),
+            namespace: self.namespace,
+            content: self.content.map(owned_nodes),
+            raw_attrs: self.raw_attrs,
+            void: self.void,
+            raw_text: self.raw_text,
         }
     }
 }
@@ -0,0 +1,92 @@
+use std::borrow::Cow;
+
+use crate::data::{SVG_ATTRIBUTE_NAMES, SVG_TAG_NAMES};
+
+/// The namespace of an element, which is svg or MathML inside `<svg>` or
+/// `<math>`, and html otherwise.
+///
+/// The names of the svg and MathML elements and attributes are in their
+/// proper case, like `foreignObject` and `viewBox`, and these elements can
+/// be self-closing like `<path />`.
+///
+/// ```
+/// use html_query_parser::{parse, Namespace, Queryable, Selector};
+///
+/// let nodes = parse(r#"<div><svg VIEWBOX="0 0 8 8"><clippath/><a href="/"></a></svg></div>"#);
+/// let svg = nodes.query(&Selector::from("svg")).unwrap();
+/// assert_eq!(svg.namespace, Namespace::Svg);
+/// assert_eq!(svg.attrs["viewBox"], "0 0 8 8");
+/// assert!(nodes.query(&Selector::from("clipPath")).is_some());
+/// assert!(nodes.query(&Selector::from("svg|a")).is_some());
+/// assert!(nodes.query(&Selector::from("html|a")).is_none());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Namespace {
+    #[default]
+    Html,
+    Svg,
+    MathMl,
+}
+
+// Convert the ascii letters of `name` to lowercase.
+fn lowercase(name: Cow<'_, str>) -> Cow<'_, str> {
+    if name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        name
+    }
+}
+
+// Find the `name` in mixed case in `names`.
+fn find_name<'a>(name: Cow<'a, str>, names: &[&'static str]) -> Cow<'a, str> {
+    match names.iter().find(|n| n.eq_ignore_ascii_case(&name)) {
+        Some(n) => Cow::Borrowed(n),
+        None => lowercase(name),
+    }
+}
+
+impl Namespace {
+    /// The url of the namespace, like `http://www.w3.org/2000/svg`.
+    pub fn url(&self) -> &'static str {
+        match self {
+            Namespace::Html => "http://www.w3.org/1999/xhtml",
+            Namespace::Svg => "http://www.w3.org/2000/svg",
+            Namespace::MathMl => "http://www.w3.org/1998/Math/MathML",
+        }
+    }
+
+    /// The prefix of the namespace in selectors, like `svg` in `svg|a`.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Namespace::Html => "html",
+            Namespace::Svg => "svg",
+            Namespace::MathMl => "math",
+        }
+    }
+
+    pub(crate) fn from_prefix(prefix: &str) -> Option<Self> {
+        [Namespace::Html, Namespace::Svg, Namespace::MathMl]
+            .into_iter()
+            .find(|namespace| namespace.prefix() == prefix)
+    }
+
+    // Correct the case of the tag name of an element in this namespace,
+    // which is kept as it is in html.
+    pub(crate) fn adjust_tag_name<'a>(&self, name: Cow<'a, str>) -> Cow<'a, str> {
+        match self {
+            Namespace::Html => name,
+            Namespace::Svg => find_name(name, &SVG_TAG_NAMES),
+            Namespace::MathMl => lowercase(name),
+        }
+    }
+
+    // Correct the case of the attribute name of an element in this
+    // namespace, which is kept as it is in html.
+    pub(crate) fn adjust_attr_name<'a>(&self, name: Cow<'a, str>) -> Cow<'a, str> {
+        match self {
+            Namespace::Html => name,
+            Namespace::Svg => find_name(name, &SVG_ATTRIBUTE_NAMES),
+            Namespace::MathMl => find_name(name, &["definitionURL"]),
+        }
+    }
+}
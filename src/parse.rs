@@ -1,140 +1,37 @@
 mod attrs;
+mod encoding;
+mod entities;
+mod error;
+mod limits;
+mod options;
+mod parser;
+mod span;
+mod tag;
 mod token;
+mod tokenizer;
+mod tree;
 
-use crate::{data::VOID_TAGS, Node};
-use token::Token;
+use std::io::{self, Read};
 
-fn html_to_stack(html: &str) -> Vec<Token> {
-    let mut chars_stack = Vec::<char>::new();
-    let mut token_stack = Vec::<Token>::new();
-    let mut in_quotes: Option<char> = None;
-    // More precisely: is in angle brackets
-    let mut in_brackets = false;
-    let mut in_comment = false;
-    for ch in html.chars() {
-        if let Some(quote) = in_quotes {
-            if ch == quote {
-                let last_char = chars_stack
-                    .last()
-                    .expect("cannot get the last char in chars stack")
-                    .clone();
-                if last_char != '\\' {
-                    in_quotes = None;
-                }
-            }
-            chars_stack.push(ch);
-        } else if in_comment {
-            chars_stack.push(ch);
-            let len = chars_stack.len();
-            if chars_stack[len - 3..len] == ['-', '-', '>'] {
-                let comment = String::from_iter(chars_stack);
-                chars_stack = Vec::new();
-                let tag = Token::from_comment(comment);
-                token_stack.push(tag);
-                in_comment = false;
-                in_brackets = false;
-            }
-        } else {
-            match ch {
-                '<' => {
-                    in_brackets = true;
-                    // In case of pushing empty text tokens to the stack
-                    if chars_stack.len() != 0 {
-                        // Turn the chars in `chars_stack` in to `String`
-                        // and clean the chars stack.
-                        let txt_text = String::from_iter(chars_stack);
-                        chars_stack = Vec::new();
-                        // Push the text we just got to the token stack.
-                        token_stack.push(Token::Text(txt_text));
-                    }
-                    chars_stack.push(ch);
-                }
-                '>' => {
-                    in_brackets = false;
-                    chars_stack.push(ch);
-                    // Turn the chars in `chars_stack` in to `String`
-                    // and clean the chars stack.
-                    let tag_text = String::from_iter(chars_stack);
-                    chars_stack = Vec::new();
-                    // Push the tag with the text we just got to the token stack.
-                    let tag = Token::from(tag_text.clone())
-                        .expect(format!("Invalid tag: {}", tag_text).as_str());
-                    token_stack.push(tag);
-                }
-                '-' => {
-                    chars_stack.push(ch);
-                    if chars_stack.len() == 4 && chars_stack == ['<', '!', '-', '-'] {
-                        in_comment = true;
-                    }
-                }
-                _ => {
-                    if in_brackets {
-                        match ch {
-                            '\'' => in_quotes = Some('\''),
-                            '\"' => in_quotes = Some('\"'),
-                            _ => {}
-                        }
-                    }
-                    chars_stack.push(ch)
-                }
-            }
-        }
-    }
-    token_stack
-}
+use crate::{Document, Node};
 
-fn stack_to_dom(token_stack: Vec<Token>) -> Vec<Node> {
-    let mut nodes: Vec<Node> = Vec::new();
-    let mut start_tokens_stack: Vec<Token> = Vec::new();
-    let mut start_token_index = 0;
-    for (i, token) in token_stack.iter().enumerate() {
-        match token {
-            Token::Start(tag, attrs) => {
-                let is_void_tag = VOID_TAGS.contains(&tag.as_str());
-                if start_tokens_stack.is_empty() {
-                    if is_void_tag {
-                        nodes.push(Node::Element {
-                            name: tag.clone(),
-                            attrs: attrs.clone(),
-                            children: Vec::new(),
-                        });
-                    } else {
-                        start_token_index = i;
-                        start_tokens_stack.push(Token::Start(tag.clone(), attrs.clone()));
-                    }
-                } else {
-                    if !is_void_tag {
-                        start_tokens_stack.push(Token::Start(tag.clone(), attrs.clone()));
-                    }
-                }
-            }
-            Token::End(tag) => {
-                let start_tag = start_tokens_stack
-                    .pop()
-                    .expect(format!("unexpected end tag: {}", tag).as_str())
-                    .into_node()
-                    .try_into_element()
-                    .unwrap();
-                if start_tokens_stack.is_empty() {
-                    nodes.push(Node::Element {
-                        name: start_tag.name,
-                        attrs: start_tag.attrs,
-                        children: stack_to_dom(token_stack[start_token_index + 1..i].to_vec()),
-                    })
-                }
-            }
-            _ => {
-                if start_tokens_stack.is_empty() {
-                    nodes.push(token.node());
-                }
-            }
-        }
-    }
-    nodes
-}
+use self::{tokenizer::StreamTokenizer, tree::TreeBuilder};
+
+pub(crate) use encoding::charset_in_content;
+pub(crate) use entities::decode as decode_entities;
+pub use encoding::Encoding;
+pub use error::{ParseError, ParseErrorKind};
+pub use limits::{LimitPolicy, Limits};
+pub use options::ParseOptions;
+pub use parser::Parser;
+pub use span::Span;
+pub use token::Token;
+pub use tokenizer::Tokenizer;
 
 /// Parse the html string and return a `Vector` of `Node`.
 ///
+/// Malformed html is repaired rather than rejected, see [`parse_with_errors`].
+///
 /// Example:
 ///
 /// ```
@@ -157,14 +54,30 @@ fn stack_to_dom(token_stack: Vec<Token>) -> Vec<Node> {
 ///             "class": "content",
 ///         },
 ///         children: [
-///             Text(
-///                 "Hello, world!",
-///             ),
+///             Text {
+///                 text: "Hello, world!",
+///                 span: None,
+///                 raw: false,
+///             },
 ///         ],
+///         span: None,
+///         attr_spans: {},
+///         namespace: Html,
+///         content: None,
+///         raw_attrs: false,
+///         void: false,
+///         raw_text: false,
 ///     },
 /// ]
 /// [
-///     Doctype,
+///     Doctype {
+///         name: Some(
+///             "html",
+///         ),
+///         public_id: None,
+///         system_id: None,
+///         span: None,
+///     },
 ///     Element {
 ///         name: "html",
 ///         attrs: {},
@@ -173,18 +86,256 @@ fn stack_to_dom(token_stack: Vec<Token>) -> Vec<Node> {
 ///                 name: "head",
 ///                 attrs: {},
 ///                 children: [],
+///                 span: None,
+///                 attr_spans: {},
+///                 namespace: Html,
+///                 content: None,
+///                 raw_attrs: false,
+///                 void: false,
+///                 raw_text: false,
 ///             },
 ///             Element {
 ///                 name: "body",
 ///                 attrs: {},
 ///                 children: [],
+///                 span: None,
+///                 attr_spans: {},
+///                 namespace: Html,
+///                 content: None,
+///                 raw_attrs: false,
+///                 void: false,
+///                 raw_text: false,
 ///             },
 ///         ],
+///         span: None,
+///         attr_spans: {},
+///         namespace: Html,
+///         content: None,
+///         raw_attrs: false,
+///         void: false,
+///         raw_text: false,
 ///     },
 /// ]
 /// ```
-pub fn parse(html: &str) -> Vec<Node> {
-    let stack = html_to_stack(html);
-    let dom = stack_to_dom(stack);
-    dom
+pub fn parse(html: &str) -> Vec<Node<'_>> {
+    parse_with(html, &ParseOptions::default())
+}
+
+/// Parse the html string like [`parse`], with the given [`ParseOptions`].
+///
+/// ```
+/// use html_query_parser::{parse_with, ParseOptions, Node};
+///
+/// let options = ParseOptions {
+///     decode_entities: false,
+///     ..ParseOptions::default()
+/// };
+/// let nodes = parse_with("Tom &amp; Jerry", &options);
+/// assert!(matches!(&nodes[0], Node::Text { text, .. } if text == "Tom &amp; Jerry"));
+/// ```
+pub fn parse_with<'a>(html: &'a str, options: &ParseOptions) -> Vec<Node<'a>> {
+    parse_with_options_and_errors(html, options, Target::Nodes).0
+}
+
+/// Parse the html string like [`parse`], and also return the errors that
+/// have been repaired, sorted by their positions.
+///
+/// Like browsers, an end tag closes the nearest open element with the same
+/// name and the elements opened after it, stray end tags are ignored, and
+/// the elements still open at the end are closed.
+///
+/// ```
+/// use html_query_parser::{parse_with_errors, Htmlifiable, ParseErrorKind};
+///
+/// let (nodes, errors) = parse_with_errors("<div><span>Hello</div></ul>");
+/// assert_eq!(nodes.html(), "<div><span>Hello</span></div>");
+/// assert_eq!(errors[0].kind, ParseErrorKind::UnclosedElement("span".to_string()));
+/// assert_eq!(errors[0].offset, 5);
+/// assert_eq!(errors[1].kind, ParseErrorKind::UnexpectedEndTag("ul".to_string()));
+/// assert_eq!(errors[1].offset, 22);
+/// ```
+pub fn parse_with_errors(html: &str) -> (Vec<Node<'_>>, Vec<ParseError>) {
+    parse_with_options_and_errors(html, &ParseOptions::default(), Target::Nodes)
+}
+
+/// Parse the html string as the content of the element `context_tag`, like
+/// setting `innerHTML` in browsers, so that the nodes are ready to be
+/// inserted into it by [`insert_to`](crate::Editable::insert_to).
+///
+/// The context is not returned itself, but decides how its content is
+/// parsed: The content of `textarea`, `script`, etc. is all text, the
+/// content of `svg` and `math` is in their namespaces, and the end tags and
+/// the implied end tags cannot close the context, like `</tbody>` in
+/// `tbody`.
+///
+/// ```
+/// use html_query_parser::{parse, parse_fragment, Editable, Htmlifiable, Node, Selector};
+///
+/// let rows = parse_fragment("<tr><td>2<tr><td>3</tbody>", "tbody");
+/// assert_eq!(rows.html(), "<tr><td>2</td></tr><tr><td>3</td></tr>");
+///
+/// let mut nodes = parse("<table><tbody><tr><td>1</td></tr></tbody></table>");
+/// for row in rows {
+///     nodes.insert_to(&Selector::from("tbody"), row);
+/// }
+/// assert_eq!(
+///     nodes.html(),
+///     "<table><tbody><tr><td>1</td></tr><tr><td>2</td></tr><tr><td>3</td></tr></tbody></table>"
+/// );
+///
+/// let text = parse_fragment("<b>Hi</b></textarea>", "textarea");
+/// assert!(matches!(&text[0], Node::Text { text, .. } if text == "<b>Hi</b></textarea>"));
+/// ```
+pub fn parse_fragment<'a>(html: &'a str, context_tag: &str) -> Vec<Node<'a>> {
+    let options = ParseOptions::default();
+    parse_with_options_and_errors(html, &options, Target::Fragment(context_tag)).0
+}
+
+/// Parse the html string as a whole document, which always has the `html`
+/// element with `head` and `body` in it, like browsers do.
+///
+/// The `html`, `head` and `body` elements are created if their tags are
+/// omitted, the elements like `meta` and `title` before the content are put
+/// in `head`, and the text and elements misplaced in a table outside of its
+/// cells are moved before the table.
+///
+/// ```
+/// use html_query_parser::{parse_document, Htmlifiable};
+///
+/// let document = parse_document("<title>App</title><table><tr><td>1</td>Oops</table>");
+/// assert_eq!(
+///     document.nodes.html(),
+///     "<html><head><title>App</title></head><body>Oops<table><tbody><tr><td>1</td></tr></tbody></table></body></html>"
+/// );
+/// ```
+pub fn parse_document(html: &str) -> Document<'_> {
+    let options = ParseOptions::default();
+    let (nodes, _) = parse_with_options_and_errors(html, &options, Target::Document);
+    Document { nodes }
+}
+
+// What the html is parsed as.
+enum Target<'c> {
+    Nodes,
+    // The content of the context element.
+    Fragment(&'c str),
+    Document,
+}
+
+fn parse_with_options_and_errors<'a>(
+    html: &'a str,
+    options: &ParseOptions,
+    target: Target,
+) -> (Vec<Node<'a>>, Vec<ParseError>) {
+    let mut tokenizer = StreamTokenizer::new(options.clone());
+    let mut tree_builder = match target {
+        Target::Nodes => TreeBuilder::new(options),
+        Target::Fragment(context) => {
+            tokenizer.start_in(context);
+            TreeBuilder::with_context(options, context)
+        }
+        Target::Document => TreeBuilder::for_document(options),
+    };
+    tokenizer.tokenize(html, &mut |token, span| {
+        tree_builder.push(token, span);
+        tree_builder.in_html()
+    });
+    parser::build(tokenizer, tree_builder)
+}
+
+/// Parse the html string like [`parse`], but fail with the first
+/// [`ParseError`] instead of repairing the html.
+///
+/// ```
+/// use html_query_parser::{try_parse, ParseErrorKind};
+///
+/// let nodes = try_parse("<p>Hello, world!</p>").unwrap();
+/// assert_eq!(nodes.len(), 1);
+///
+/// let err = try_parse("<p>Hello, world!</p></div>").unwrap_err();
+/// assert_eq!(err.kind, ParseErrorKind::UnexpectedEndTag("div".to_string()));
+/// assert_eq!(err.offset, 20);
+/// ```
+pub fn try_parse(html: &str) -> Result<Vec<Node<'_>>, ParseError> {
+    try_parse_with(html, &ParseOptions::default())
+}
+
+/// Parse the html string like [`try_parse`], with the given [`ParseOptions`].
+///
+/// In the [`strict`](ParseOptions::strict) mode, the parsing stops right at
+/// the first error, while the whole html is parsed otherwise.
+///
+/// ```
+/// use html_query_parser::{try_parse_with, ParseErrorKind, ParseOptions};
+///
+/// let options = ParseOptions {
+///     strict: true,
+///     ..ParseOptions::default()
+/// };
+/// let err = try_parse_with("<div><span>Hi</div></span>", &options).unwrap_err();
+/// assert_eq!(err.kind, ParseErrorKind::UnclosedElement("span".to_string()));
+/// ```
+pub fn try_parse_with<'a>(
+    html: &'a str,
+    options: &ParseOptions,
+) -> Result<Vec<Node<'a>>, ParseError> {
+    let (nodes, errors) = parse_with_options_and_errors(html, options, Target::Nodes);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(nodes),
+    }
+}
+
+/// Parse the html read from `reader` in chunks with a [`Parser`].
+///
+/// ```
+/// use html_query_parser::{parse_reader, Htmlifiable};
+///
+/// let html: &[u8] = b"<ul><li>One<li>Two</ul>";
+/// let nodes = parse_reader(html).unwrap();
+/// assert_eq!(nodes.html(), "<ul><li>One</li><li>Two</li></ul>");
+/// ```
+pub fn parse_reader(mut reader: impl Read) -> io::Result<Vec<Node<'static>>> {
+    let mut parser = Parser::new();
+    let mut buffer = [0; 8192];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(parser.finish()),
+            Ok(len) => parser.feed(&buffer[..len]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Parse the html bytes in an unknown encoding, like a downloaded page, and
+/// return the nodes with the [`Encoding`] detected.
+///
+/// Like browsers, the encoding is detected by the byte order mark, or else
+/// by `<meta charset>` or `<meta http-equiv="Content-Type">` in the first
+/// 1024 bytes. Without either of them, the html is read as UTF-8 if it is
+/// valid UTF-8, or as windows-1252 otherwise.
+///
+/// ```
+/// use html_query_parser::{parse_bytes, Encoding, Htmlifiable};
+///
+/// let html = b"<meta charset=\"iso-8859-1\"><p>Caf\xE9 \x80</p>";
+/// let (nodes, encoding) = parse_bytes(html);
+/// assert_eq!(encoding, Encoding::Windows1252);
+/// assert_eq!(nodes[1].html(), "<p>Caf\u{E9} \u{20AC}</p>");
+///
+/// let (nodes, encoding) = parse_bytes(b"\xFF\xFE<\0p\0>\0H\0i\0<\0/\0p\0>\0");
+/// assert_eq!(encoding, Encoding::Utf16Le);
+/// assert_eq!(nodes.html(), "<p>Hi</p>");
+/// ```
+pub fn parse_bytes(bytes: &[u8]) -> (Vec<Node<'static>>, Encoding) {
+    let (encoding, bom_len) = Encoding::detect(bytes);
+    let bytes = &bytes[bom_len..];
+    let mut parser = Parser::new();
+    match encoding {
+        // Invalid UTF-8 is replaced by the parser.
+        Encoding::Utf8 => parser.feed(bytes),
+        encoding => parser.feed(encoding.decode(bytes)),
+    }
+    (parser.finish(), encoding)
 }
@@ -1,125 +1,230 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashSet, ops::Range};
 
-// Let's take `<img src="example.png" alt=image>` for example.
-enum AttrPos {
+use crate::parse::{entities, limits, ParseErrorKind, ParseOptions};
+
+use super::span::{Location, Span};
+
+// The states of a tag after `<`, like the html spec has, taking
+// `<img src="example.png" alt=image />` for example. A quote only starts a
+// value after `=`, and `>` closes the tag outside quoted values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagState {
+    // This including `img`
+    TagName,
+    // This including the spaces and `/` before `src`, `alt`
+    BeforeKey,
     // This including `src`, `alt`
     Key,
-    // This including `=`
-    Equal,
-    // This including `example.png`, `image`
-    Value(Option<char>),
-    // This including ` `
-    Space,
+    // This including the spaces after a key, like in `src = "example.png"`
+    AfterKey,
+    // This including `=` and the spaces after it
+    BeforeValue,
+    // This including `"example.png` and `image`
+    Value(Option<u8>),
+    // This including the closing `"` of `"example.png"`
+    AfterQuote,
 }
 
-// Valid `attr_str` like: `src="example.png" alt=example disabled`
-pub fn parse(attr_str: String) -> HashMap<String, String> {
-    let mut chars_stack: Vec<char> = Vec::new();
-    let mut key_stack: Vec<String> = Vec::new();
-    let mut value_stack: Vec<String> = Vec::new();
-    let mut attr_pos = AttrPos::Key;
-    for ch in attr_str.chars() {
-        match attr_pos {
-            AttrPos::Key => match ch {
-                '=' => {
-                    attr_pos = AttrPos::Equal;
-                    let key = String::from_iter(chars_stack);
-                    chars_stack = Vec::new();
-                    key_stack.push(key)
-                }
-                ' ' => {
-                    attr_pos = AttrPos::Space;
-                    let key = String::from_iter(chars_stack);
-                    chars_stack = Vec::new();
-                    key_stack.push(key);
-                    value_stack.push(String::new())
-                }
-                _ => chars_stack.push(ch),
-            },
-            AttrPos::Equal => match ch {
-                '\'' => attr_pos = AttrPos::Value(Some('\'')),
-                '\"' => attr_pos = AttrPos::Value(Some('\"')),
-                _ => {
-                    attr_pos = AttrPos::Value(None);
-                    chars_stack.push(ch)
-                }
-            },
-            AttrPos::Value(delimiter) => match delimiter {
-                None => {
-                    if ch == ' ' {
-                        attr_pos = AttrPos::Space;
-                        let value = String::from_iter(chars_stack);
-                        chars_stack = Vec::new();
-                        value_stack.push(value)
-                    } else {
-                        chars_stack.push(ch);
-                    }
-                }
-                Some(quote) => {
-                    if ch == quote {
-                        if chars_stack.len() == 0 {
-                            value_stack.push(String::new());
-                            attr_pos = AttrPos::Space;
-                            continue;
-                        }
-                        let last_char = chars_stack
-                            .last()
-                            .expect("cannot accesss the last char in `chars_stack`");
-                        if last_char == &'\\' {
-                            chars_stack.push(ch);
-                            continue;
-                        } else {
-                            attr_pos = AttrPos::Space;
-                            let value = String::from_iter(chars_stack);
-                            chars_stack = Vec::new();
-                            value_stack.push(value)
-                        }
-                    } else {
-                        chars_stack.push(ch)
-                    }
-                }
-            },
-            AttrPos::Space => {
-                if ch != ' ' {
-                    attr_pos = AttrPos::Key;
-                    chars_stack.push(ch);
-                }
-            }
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+impl TagState {
+    // The state after `byte`, which is not `>` closing the tag.
+    pub fn next(self, byte: u8) -> Self {
+        let is_separator = is_space(byte) || byte == b'/';
+        match self {
+            TagState::TagName if is_separator => TagState::BeforeKey,
+            TagState::TagName => TagState::TagName,
+            TagState::BeforeKey | TagState::AfterQuote if is_separator => TagState::BeforeKey,
+            // `=` at the start of a key is kept in it.
+            TagState::BeforeKey | TagState::AfterQuote => TagState::Key,
+            TagState::Key | TagState::AfterKey if byte == b'=' => TagState::BeforeValue,
+            TagState::Key | TagState::AfterKey if byte == b'/' => TagState::BeforeKey,
+            TagState::Key | TagState::AfterKey if is_space(byte) => TagState::AfterKey,
+            TagState::Key | TagState::AfterKey => TagState::Key,
+            TagState::BeforeValue if is_space(byte) => TagState::BeforeValue,
+            TagState::BeforeValue if byte == b'\'' || byte == b'"' => TagState::Value(Some(byte)),
+            TagState::BeforeValue => TagState::Value(None),
+            TagState::Value(None) if is_space(byte) => TagState::BeforeKey,
+            TagState::Value(Some(quote)) if byte == quote => TagState::AfterQuote,
+            TagState::Value(_) => self,
+        }
+    }
+}
+
+// Drop the attributes whose keys are the same as earlier ones but for their
+// case, so that the first of them wins like in browsers, along with their
+// spans if any. The few keys of most tags are compared without
+// hashing them.
+fn dedup(attrs: &mut AttrList<'_>, spans: &mut Vec<Span>, errors: &mut Vec<ParseErrorKind>) {
+    if attrs.len() < 2 {
+        return;
+    }
+    let duplicates: Vec<usize> = if attrs.len() > 16 {
+        let mut lowercase_keys = HashSet::new();
+        (0..attrs.len())
+            .filter(|&i| !lowercase_keys.insert(attrs[i].0.to_ascii_lowercase()))
+            .collect()
+    } else {
+        (1..attrs.len())
+            .filter(|&i| {
+                attrs[..i]
+                    .iter()
+                    .any(|(key, _)| key.eq_ignore_ascii_case(&attrs[i].0))
+            })
+            .collect()
+    };
+    for &i in &duplicates {
+        errors.push(ParseErrorKind::DuplicateAttribute(attrs[i].0.to_string()));
+    }
+    for &i in duplicates.iter().rev() {
+        attrs.remove(i);
+        if !spans.is_empty() {
+            spans.remove(i);
         }
     }
+}
 
-    let err_info = format!("cannot parse the attributes: {}", attr_str);
-    let err_info = err_info.as_str();
+// The keys and values of attributes in order.
+pub type AttrList<'a> = Vec<(Cow<'a, str>, Cow<'a, str>)>;
 
-    if !chars_stack.is_empty() {
-        let str = String::from_iter(chars_stack);
-        match attr_pos {
-            AttrPos::Key => key_stack.push(str),
-            AttrPos::Value(delimiter) => {
-                if let None = delimiter {
-                    value_stack.push(str);
-                } else {
-                    panic!("{}", err_info)
+// Valid `attr_str` like: ` src="example.png" alt=example disabled /`
+//
+// Invalid attributes are still parsed as far as possible,
+// and the problems are pushed to `errors`.
+//
+// The keys and values are returned in order, with their spans if
+// `location`, where `attr_str` starts, is given, and whether the tag is
+// self-closing by the `/` at the end. Since all the delimiters are ascii,
+// they are sliced straight out of `attr_str`.
+pub fn parse<'a>(
+    attr_str: &'a str,
+    location: Option<Location>,
+    options: &ParseOptions,
+    errors: &mut Vec<ParseErrorKind>,
+) -> (AttrList<'a>, Vec<Span>, bool) {
+    // Most tags have no attributes.
+    if attr_str.is_empty() {
+        return (AttrList::new(), Vec::new(), false);
+    }
+    let bytes = attr_str.as_bytes();
+    let mut attrs = AttrList::new();
+    let mut spans = Vec::new();
+    // The locations are found from one attribute to the next.
+    let mut attr_location = location;
+    let mut scanned = 0;
+    // Byte offsets of the current attribute, and of its value, or of the
+    // end of `=` before the value starts.
+    let mut attr_start = 0;
+    let mut value_start = 0;
+    let mut key = "";
+    let mut state = TagState::BeforeKey;
+    let mut push = |key: &'a str, value: &'a str, range: Range<usize>| {
+        attrs.push((Cow::Borrowed(key), Cow::Borrowed(value)));
+        if let (Some(attr_location), Some(location)) = (&mut attr_location, location) {
+            *attr_location = attr_location.after(&attr_str[scanned..range.start]);
+            scanned = range.start;
+            spans.push(Span::new(*attr_location, location.offset + range.end));
+        }
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        // Quoted values are skipped at once, as only the quote ends them.
+        if let TagState::Value(Some(quote)) = state {
+            match bytes[i..].iter().position(|&byte| byte == quote) {
+                Some(len) => i += len,
+                None => break,
+            }
+        }
+        let byte = bytes[i];
+        let next = state.next(byte);
+        match state {
+            TagState::BeforeKey | TagState::AfterKey | TagState::AfterQuote
+                if next == TagState::Key =>
+            {
+                match state {
+                    TagState::AfterKey => push(key, "", attr_start..attr_start + key.len()),
+                    // Like `c` in `a="b"c`.
+                    TagState::AfterQuote => errors.push(ParseErrorKind::InvalidAttributes),
+                    _ => {}
                 }
+                attr_start = i;
+            }
+            TagState::Key if next != TagState::Key => {
+                key = &attr_str[attr_start..i];
+                value_start = i + 1;
+                if next == TagState::BeforeKey {
+                    push(key, "", attr_start..i);
+                }
+            }
+            TagState::AfterKey if next == TagState::BeforeValue => value_start = i + 1,
+            TagState::AfterKey if next == TagState::BeforeKey => {
+                push(key, "", attr_start..attr_start + key.len());
+            }
+            TagState::BeforeValue if next == TagState::Value(None) => value_start = i,
+            TagState::BeforeValue if next != TagState::BeforeValue => value_start = i + 1,
+            TagState::Value(None) if next != state => {
+                push(key, &attr_str[value_start..i], attr_start..i);
+            }
+            TagState::Value(Some(_)) if next != state => {
+                push(key, &attr_str[value_start..i], attr_start..i + 1);
             }
             _ => {}
         }
+        state = next;
+        i += 1;
+    }
+
+    let end = attr_str.len();
+    match state {
+        TagState::Key => push(&attr_str[attr_start..], "", attr_start..end),
+        TagState::AfterKey => push(key, "", attr_start..attr_start + key.len()),
+        TagState::BeforeValue => push(key, "", attr_start..value_start),
+        TagState::Value(quote) => {
+            if quote.is_some() {
+                errors.push(ParseErrorKind::InvalidAttributes);
+            }
+            push(key, &attr_str[value_start..], attr_start..end);
+        }
+        TagState::TagName | TagState::BeforeKey | TagState::AfterQuote => {}
     }
+    let self_closing = state == TagState::BeforeKey && attr_str.ends_with('/');
 
-    if key_stack.len() != value_stack.len() {
-        panic!(
-            "{}\nkey:\t{:?}\nvalue:\t{:?}",
-            err_info, key_stack, value_stack
-        )
+    dedup(&mut attrs, &mut spans, errors);
+    if options.lowercase_names {
+        for (key, _) in attrs.iter_mut() {
+            if key.bytes().any(|byte| byte.is_ascii_uppercase()) {
+                *key = Cow::Owned(key.to_ascii_lowercase());
+            }
+        }
     }
 
-    let mut hashmap = HashMap::new();
-    let len = key_stack.len();
-    for _ in 0..len {
-        hashmap.insert(
-            key_stack.pop().expect(err_info),
-            value_stack.pop().expect(err_info),
+    if options.decode_entities {
+        let mut reference_errors = Vec::new();
+        for (_, value) in attrs.iter_mut() {
+            if let Cow::Borrowed(text) = value {
+                *value = entities::decode(text, true, &mut reference_errors);
+            }
+        }
+        errors.extend(
+            reference_errors
+                .into_iter()
+                .map(|_| ParseErrorKind::InvalidCharacterReference),
         );
     }
-    hashmap
+
+    let limits = &options.limits;
+    if attrs.len() > limits.max_attrs {
+        errors.push(ParseErrorKind::TooManyAttributes);
+        attrs.truncate(limits.max_attrs);
+        spans.truncate(limits.max_attrs);
+    }
+    for (_, value) in attrs.iter_mut() {
+        if limits::truncate(value, limits.max_attr_len) {
+            errors.push(ParseErrorKind::AttributeTooLong);
+        }
+    }
+
+    (attrs, spans, self_closing)
 }
@@ -0,0 +1,298 @@
+use super::entities::WINDOWS_1252;
+
+/// Character encoding of the html bytes, detected by
+/// [`parse_bytes`](crate::parse_bytes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Which is also used for `iso-8859-1` and `us-ascii`, like browsers do.
+    Windows1252,
+}
+
+// How far `<meta>` is looked for.
+const PRESCAN_LEN: usize = 1024;
+
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+fn is_space_char(ch: char) -> bool {
+    ch.is_ascii() && is_space(ch as u8)
+}
+
+impl Encoding {
+    /// The name of the encoding, like `UTF-8`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    // Get the encoding by one of its labels, like `latin1`.
+    fn from_label(label: &[u8]) -> Option<Self> {
+        let label = String::from_utf8_lossy(label)
+            .trim_matches(is_space_char)
+            .to_ascii_lowercase();
+        let encoding = match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Encoding::Utf8,
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Encoding::Utf16Le,
+            "unicodefffe" | "utf-16be" => Encoding::Utf16Be,
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252"
+            | "x-user-defined" => Encoding::Windows1252,
+            _ => return None,
+        };
+        Some(encoding)
+    }
+
+    /// Detect the encoding of `bytes` by the byte order mark, or else by
+    /// `<meta charset>` in the first 1024 bytes, and return it with the
+    /// length of the byte order mark.
+    ///
+    /// Without either of them, it is UTF-8 if `bytes` is valid UTF-8, or
+    /// windows-1252 otherwise.
+    pub(crate) fn detect(bytes: &[u8]) -> (Self, usize) {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => return (Encoding::Utf8, 3),
+            [0xFE, 0xFF, ..] => return (Encoding::Utf16Be, 2),
+            [0xFF, 0xFE, ..] => return (Encoding::Utf16Le, 2),
+            _ => {}
+        }
+        let encoding = match prescan(&bytes[..bytes.len().min(PRESCAN_LEN)]) {
+            Some(encoding) => encoding,
+            None if std::str::from_utf8(bytes).is_ok() => Encoding::Utf8,
+            None => Encoding::Windows1252,
+        };
+        (encoding, 0)
+    }
+
+    /// Decode `bytes` without the byte order mark, where invalid bytes are
+    /// replaced by `U+FFFD`.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+            Encoding::Windows1252 => bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x80..=0x9F => WINDOWS_1252[byte as usize - 0x80],
+                    _ => byte as char,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    let mut text: String = char::decode_utf16(units)
+        .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    // A byte left at the end cannot make a char.
+    if bytes.len() % 2 == 1 {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    text
+}
+
+// Look for the encoding in `<meta charset="...">` or
+// `<meta http-equiv="Content-Type" content="text/html; charset=...">` in
+// `bytes`, skipping the comments and the other tags like the html spec does.
+fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    let mut scanner = Scanner { bytes, pos: 0 };
+    while let Some(i) = bytes[scanner.pos..].iter().position(|&byte| byte == b'<') {
+        scanner.pos += i;
+        let rest = &bytes[scanner.pos..];
+        if rest.starts_with(b"<!--") {
+            scanner.skip_to(b"-->", 2)?;
+        } else if rest.len() > 5
+            && rest[..5].eq_ignore_ascii_case(b"<meta")
+            && (is_space(rest[5]) || rest[5] == b'/')
+        {
+            scanner.pos += 5;
+            if let Some(encoding) = scanner.meta()? {
+                return Some(encoding);
+            }
+        } else if rest.len() > 1 && rest[1].is_ascii_alphabetic()
+            || rest.len() > 2 && rest.starts_with(b"</") && rest[2].is_ascii_alphabetic()
+        {
+            // Skip the other tags with their attributes.
+            scanner.pos += 1;
+            while !is_space(scanner.peek()?) && scanner.peek()? != b'>' {
+                scanner.pos += 1;
+            }
+            while scanner.attribute()?.is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            scanner.skip_to(b">", 1)?;
+        }
+        scanner.pos += 1;
+    }
+    None
+}
+
+// Cursor over the bytes being prescanned, where `None` is returned if the
+// end is reached, which aborts the prescan.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    // Move to the last byte of the first `end` after `from` bytes.
+    fn skip_to(&mut self, end: &[u8], from: usize) -> Option<()> {
+        let start = self.pos + from;
+        let i = self
+            .bytes
+            .get(start..)?
+            .windows(end.len())
+            .position(|window| window == end)?;
+        self.pos = start + i + end.len() - 1;
+        Some(())
+    }
+
+    fn skip_spaces(&mut self) -> Option<u8> {
+        while is_space(self.peek()?) {
+            self.pos += 1;
+        }
+        self.peek()
+    }
+
+    // Take the encoding from the attributes of `<meta>`, if any.
+    fn meta(&mut self) -> Option<Option<Encoding>> {
+        let mut names = Vec::new();
+        let mut got_pragma = false;
+        let mut need_pragma = None;
+        let mut charset = None;
+        while let Some((name, value)) = self.attribute()? {
+            if names.contains(&name) {
+                continue;
+            }
+            match name.as_slice() {
+                b"http-equiv" => got_pragma |= value.eq_ignore_ascii_case(b"content-type"),
+                b"content" if charset.is_none() => {
+                    if let Some(encoding) =
+                        charset_in_content(&value).and_then(Encoding::from_label)
+                    {
+                        charset = Some(Some(encoding));
+                        need_pragma = Some(true);
+                    }
+                }
+                b"charset" => {
+                    charset = Some(Encoding::from_label(&value));
+                    need_pragma = Some(false);
+                }
+                _ => {}
+            }
+            names.push(name);
+        }
+        if need_pragma == Some(true) && !got_pragma {
+            return Some(None);
+        }
+        // The bytes being read as ascii cannot be in UTF-16.
+        Some(charset.flatten().map(|encoding| match encoding {
+            Encoding::Utf16Le | Encoding::Utf16Be => Encoding::Utf8,
+            encoding => encoding,
+        }))
+    }
+
+    // Take the next attribute of the tag, with its name and value in
+    // lowercase, or `None` at the end of the tag.
+    fn attribute(&mut self) -> Option<Option<(Vec<u8>, Vec<u8>)>> {
+        while is_space(self.peek()?) || self.peek()? == b'/' {
+            self.pos += 1;
+        }
+        if self.peek()? == b'>' {
+            return Some(None);
+        }
+        let mut name = Vec::new();
+        let mut value = Vec::new();
+        loop {
+            match self.peek()? {
+                b'=' if !name.is_empty() => break,
+                byte if is_space(byte) => {
+                    if self.skip_spaces()? != b'=' {
+                        return Some(Some((name, value)));
+                    }
+                    break;
+                }
+                b'/' | b'>' => return Some(Some((name, value))),
+                byte => name.push(byte.to_ascii_lowercase()),
+            }
+            self.pos += 1;
+        }
+        // Skip `=`.
+        self.pos += 1;
+        match self.skip_spaces()? {
+            quote @ (b'"' | b'\'') => loop {
+                self.pos += 1;
+                match self.peek()? {
+                    byte if byte == quote => {
+                        self.pos += 1;
+                        return Some(Some((name, value)));
+                    }
+                    byte => value.push(byte.to_ascii_lowercase()),
+                }
+            },
+            b'>' => return Some(Some((name, value))),
+            _ => {}
+        }
+        loop {
+            match self.peek()? {
+                byte if is_space(byte) || byte == b'>' => return Some(Some((name, value))),
+                byte => value.push(byte.to_ascii_lowercase()),
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+// Find the label after `charset=` in the `content` of `<meta>`, like
+// `text/html; charset=utf-8`.
+pub(crate) fn charset_in_content(content: &[u8]) -> Option<&[u8]> {
+    let mut pos = 0;
+    loop {
+        let i = content[pos..]
+            .windows(7)
+            .position(|window| window.eq_ignore_ascii_case(b"charset"))?;
+        pos += i + 7;
+        while content.get(pos).is_some_and(|&byte| is_space(byte)) {
+            pos += 1;
+        }
+        if content.get(pos) == Some(&b'=') {
+            break;
+        }
+    }
+    pos += 1;
+    while content.get(pos).is_some_and(|&byte| is_space(byte)) {
+        pos += 1;
+    }
+    let rest = &content[pos..];
+    match rest.first()? {
+        quote @ (b'"' | b'\'') => {
+            let end = rest[1..].iter().position(|byte| byte == quote)?;
+            Some(&rest[1..end + 1])
+        }
+        _ => {
+            let end = rest
+                .iter()
+                .position(|&byte| is_space(byte) || byte == b';')
+                .unwrap_or(rest.len());
+            Some(&rest[..end])
+        }
+    }
+}
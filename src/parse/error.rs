@@ -0,0 +1,132 @@
+use std::{error::Error, fmt};
+
+use super::span::Location;
+
+/// What went wrong while parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A tag that cannot be recognized, like `<>` or `</ >`.
+    InvalidTag,
+    /// A tag that is not closed by `>` before the end of the html, like
+    /// `<p class="a`. It is dropped.
+    EofInTag,
+    /// Attributes that cannot be split into keys and values, like `a="b"c`.
+    InvalidAttributes,
+    /// An attribute with the same name as an earlier one of the tag, but
+    /// for the case. It is dropped.
+    DuplicateAttribute(String),
+    /// A character reference that is unknown, out of range, or not closed
+    /// by `;`, like `&#0;` or `&nbsp`.
+    InvalidCharacterReference,
+    /// A doctype without a name, or with malformed identifiers.
+    InvalidDoctype,
+    /// Markup like `<!ELEMENT br EMPTY>` that is kept as a comment.
+    BogusComment,
+    /// A comment that closes right after it opens, like `<!-->`.
+    AbruptComment,
+    /// A comment closed by `--!>` instead of `-->`.
+    IncorrectlyClosedComment,
+    /// A comment or CDATA section that is not closed, which then takes the
+    /// rest of the html.
+    UnclosedComment,
+    /// An end tag without a matching start tag. It is ignored.
+    UnexpectedEndTag(String),
+    /// An element that is not closed by its own end tag, but implicitly
+    /// by the end tag of an ancestor or by the end of the input.
+    UnclosedElement(String),
+    /// A start tag where it is not allowed in a document, like a second
+    /// `<body>` whose attributes are added to the first one, or `<meta>`
+    /// after `head`, which is moved into it.
+    UnexpectedStartTag(String),
+    /// Text or an element inside `table` but outside its cells, which is
+    /// moved before the table in a document.
+    MisplacedTableContent,
+    /// An element nested deeper than [`Limits::max_depth`](crate::Limits::max_depth).
+    TooDeep,
+    /// More nodes than [`Limits::max_nodes`](crate::Limits::max_nodes).
+    TooManyNodes,
+    /// An element with more attributes than [`Limits::max_attrs`](crate::Limits::max_attrs).
+    TooManyAttributes,
+    /// An attribute value longer than [`Limits::max_attr_len`](crate::Limits::max_attr_len).
+    AttributeTooLong,
+    /// A text longer than [`Limits::max_text_len`](crate::Limits::max_text_len).
+    TextTooLong,
+    /// Input longer than [`Limits::max_input_len`](crate::Limits::max_input_len).
+    InputTooLarge,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::InvalidTag => write!(f, "invalid tag"),
+            ParseErrorKind::EofInTag => write!(f, "end of html in tag"),
+            ParseErrorKind::InvalidAttributes => write!(f, "cannot parse the attributes"),
+            ParseErrorKind::DuplicateAttribute(name) => write!(f, "duplicate attribute: {}", name),
+            ParseErrorKind::InvalidCharacterReference => write!(f, "invalid character reference"),
+            ParseErrorKind::InvalidDoctype => write!(f, "invalid doctype"),
+            ParseErrorKind::BogusComment => write!(f, "bogus comment"),
+            ParseErrorKind::AbruptComment => write!(f, "abrupt closing of empty comment"),
+            ParseErrorKind::IncorrectlyClosedComment => write!(f, "incorrectly closed comment"),
+            ParseErrorKind::UnclosedComment => write!(f, "unclosed comment"),
+            ParseErrorKind::UnexpectedEndTag(name) => write!(f, "unexpected end tag: {}", name),
+            ParseErrorKind::UnclosedElement(name) => write!(f, "unclosed element: {}", name),
+            ParseErrorKind::UnexpectedStartTag(name) => write!(f, "unexpected start tag: {}", name),
+            ParseErrorKind::MisplacedTableContent => write!(f, "content misplaced in table"),
+            ParseErrorKind::TooDeep => write!(f, "element nested too deep"),
+            ParseErrorKind::TooManyNodes => write!(f, "too many nodes"),
+            ParseErrorKind::TooManyAttributes => write!(f, "too many attributes"),
+            ParseErrorKind::AttributeTooLong => write!(f, "attribute value too long"),
+            ParseErrorKind::TextTooLong => write!(f, "text too long"),
+            ParseErrorKind::InputTooLarge => write!(f, "input too large"),
+        }
+    }
+}
+
+/// Error found while parsing, with the position where it occurred.
+///
+/// ```
+/// use html_query_parser::{try_parse, ParseErrorKind};
+///
+/// let err = try_parse("<p></p>\n  </span>").unwrap_err();
+/// assert_eq!(err.kind, ParseErrorKind::UnexpectedEndTag("span".to_string()));
+/// assert_eq!(err.offset, 10);
+/// assert_eq!((err.line, err.column), (2, 3));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Byte offset of the offending token in the input.
+    pub offset: usize,
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Column number in chars, starting from 1.
+    pub column: usize,
+}
+
+impl ParseError {
+    /// Sort the errors by their positions.
+    pub(crate) fn sorted(mut errors: Vec<(ParseErrorKind, Location)>) -> Vec<Self> {
+        errors.sort_by_key(|(_, location)| location.offset);
+        errors
+            .into_iter()
+            .map(|(kind, location)| ParseError {
+                kind,
+                offset: location.offset,
+                line: location.line,
+                column: location.column,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl Error for ParseError {}
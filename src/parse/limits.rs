@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+
+/// What to do when the html exceeds one of the [`Limits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitPolicy {
+    /// Stop at the first limit exceeded, like at an error in the strict
+    /// mode, so that only the nodes before it are returned.
+    Error,
+    /// Drop or cut off what exceeds the limits and go on.
+    Truncate,
+}
+
+/// Limits on the size of the html and the dom, to parse untrusted html
+/// safely.
+///
+/// Each limit exceeded is reported by a distinct [`ParseErrorKind`](crate::ParseErrorKind),
+/// and then handled by the [`policy`](Limits::policy). The input and the
+/// nodes over their limits are always dropped, since the rest of the html
+/// is cut off either way.
+///
+/// ```
+/// use html_query_parser::{try_parse_with, Htmlifiable, Limits, ParseErrorKind, ParseOptions, Parser};
+///
+/// let options = ParseOptions {
+///     limits: Limits {
+///         max_depth: 2,
+///         max_attrs: 1,
+///         ..Limits::default()
+///     },
+///     ..ParseOptions::default()
+/// };
+/// let mut parser = Parser::with_options(&options);
+/// parser.feed(r#"<ul><li id="a" class="b"><b>One</b></li></ul>"#);
+/// let (nodes, errors) = parser.finish_with_errors();
+/// assert_eq!(nodes.html(), r#"<ul><li id="a">One</li></ul>"#);
+/// assert_eq!(errors[0].kind, ParseErrorKind::TooManyAttributes);
+/// assert_eq!(errors[1].kind, ParseErrorKind::TooDeep);
+///
+/// let err = try_parse_with("<b><i><u>Hi</u></i></b>", &options).unwrap_err();
+/// assert_eq!(err.kind, ParseErrorKind::TooDeep);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of nested elements. Elements nested deeper are
+    /// dropped, with their content moved to their parent.
+    ///
+    /// Default: `512`, which keeps dropping, cloning and
+    /// [`into_owned`](crate::Node::into_owned) from overflowing the stack,
+    /// since they still recurse once per level unlike converting, querying
+    /// and editing the dom. Only lift it for html whose depth fits in the
+    /// stack of the thread that handles the dom.
+    pub max_depth: usize,
+    /// Maximum number of nodes. The rest of the html is dropped.
+    ///
+    /// Default: no limit
+    pub max_nodes: usize,
+    /// Maximum number of attributes of an element. The rest of them are
+    /// dropped.
+    ///
+    /// Default: no limit
+    pub max_attrs: usize,
+    /// Maximum length in bytes of an attribute value, which is cut off.
+    ///
+    /// Default: no limit
+    pub max_attr_len: usize,
+    /// Maximum length in bytes of a text, comment or CDATA section, which
+    /// is cut off.
+    ///
+    /// Default: no limit
+    pub max_text_len: usize,
+    /// Maximum length in bytes of the whole input. The rest of it is
+    /// dropped.
+    ///
+    /// Default: no limit
+    pub max_input_len: usize,
+    /// Default: [`LimitPolicy::Truncate`]
+    pub policy: LimitPolicy,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 512,
+            max_nodes: usize::MAX,
+            max_attrs: usize::MAX,
+            max_attr_len: usize::MAX,
+            max_text_len: usize::MAX,
+            max_input_len: usize::MAX,
+            policy: LimitPolicy::Truncate,
+        }
+    }
+}
+
+// Cut `text` off at the char boundary before `len` bytes, and return
+// whether it is longer than that.
+pub fn truncate(text: &mut Cow<'_, str>, len: usize) -> bool {
+    if text.len() <= len {
+        return false;
+    }
+    let end = floor_char_boundary(text, len);
+    match text {
+        Cow::Borrowed(text) => *text = &text[..end],
+        Cow::Owned(text) => text.truncate(end),
+    }
+    true
+}
+
+// The largest char boundary of `text` that is not after `index`.
+pub fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    if index >= text.len() {
+        return text.len();
+    }
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
@@ -0,0 +1,106 @@
+use crate::{
+    data::{RAW_TEXT_TAGS, VOID_TAGS},
+    parse::{LimitPolicy, Limits, ParseErrorKind},
+};
+
+/// Options to control how the html is parsed.
+///
+/// ```
+/// use html_query_parser::{parse_with, Htmlifiable, ParseOptions};
+///
+/// let options = ParseOptions {
+///     decode_entities: false,
+///     ..ParseOptions::default()
+/// };
+/// let nodes = parse_with("<p>&lt;br&gt;</p>", &options);
+/// assert_eq!(nodes.html(), "<p>&lt;br&gt;</p>");
+///
+/// let options = ParseOptions {
+///     keep_comments: false,
+///     keep_whitespace: false,
+///     lowercase_names: true,
+///     ..ParseOptions::default()
+/// };
+/// let nodes = parse_with("<UL>\n  <!-- items -->\n  <LI CLASS=a>One</LI>\n</UL>", &options);
+/// assert_eq!(nodes.html(), r#"<ul><li class="a">One</li></ul>"#);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Decode the character references like `&amp;` in text and attribute
+    /// values. If disabled, they are kept as they are in the source, and
+    /// `html()` writes them as they are instead of escaping them again.
+    ///
+    /// Default: `true`
+    pub decode_entities: bool,
+    /// Record the [`Span`](crate::Span) of every node and attribute, which
+    /// can be got by [`Node::span`](crate::Node::span) and
+    /// [`Element::attr_spans`](crate::Element::attr_spans), and are needed
+    /// by [`Htmlifiable::lossless_html`](crate::Htmlifiable::lossless_html).
+    ///
+    /// Default: `false`
+    pub spans: bool,
+    /// Keep the comments in the dom.
+    ///
+    /// Default: `true`
+    pub keep_comments: bool,
+    /// Keep the text nodes that only have whitespace in the dom, which
+    /// [`Editable::trim`](crate::Editable::trim) would remove.
+    ///
+    /// Default: `true`
+    pub keep_whitespace: bool,
+    /// Convert the names of tags and attributes to lowercase.
+    ///
+    /// Default: `false`
+    pub lowercase_names: bool,
+    /// Elements that have no content or end tag, like `br`.
+    ///
+    /// Default: the void elements of html
+    pub void_tags: Vec<String>,
+    /// Elements whose content is text till their end tags, without
+    /// character references decoded, like `script`.
+    ///
+    /// Default: `iframe`, `noembed`, `noframes`, `script`, `style` and `xmp`
+    pub raw_text_tags: Vec<String>,
+    /// Stop at the first error instead of repairing it. Then only the nodes
+    /// before it are returned, and it is the only error reported, like by
+    /// [`try_parse_with`](crate::try_parse_with).
+    ///
+    /// Default: `false`
+    pub strict: bool,
+    /// Limits on the size of the html and the dom.
+    ///
+    /// Default: [`Limits::default`]
+    pub limits: Limits,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            decode_entities: true,
+            spans: false,
+            keep_comments: true,
+            keep_whitespace: true,
+            lowercase_names: false,
+            void_tags: VOID_TAGS.iter().map(|tag| tag.to_string()).collect(),
+            raw_text_tags: RAW_TEXT_TAGS.iter().map(|tag| tag.to_string()).collect(),
+            strict: false,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl ParseOptions {
+    // Whether the parsing stops at the error rather than repairing it.
+    pub(crate) fn stops_at(&self, kind: &ParseErrorKind) -> bool {
+        match kind {
+            ParseErrorKind::InputTooLarge | ParseErrorKind::TooManyNodes => true,
+            ParseErrorKind::TooDeep
+            | ParseErrorKind::TooManyAttributes
+            | ParseErrorKind::AttributeTooLong
+            | ParseErrorKind::TextTooLong => {
+                self.strict || self.limits.policy == LimitPolicy::Error
+            }
+            _ => self.strict,
+        }
+    }
+}
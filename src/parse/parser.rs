@@ -0,0 +1,139 @@
+use std::{mem, str};
+
+use crate::{
+    parse::{ParseError, ParseOptions},
+    Node,
+};
+
+use super::{tokenizer::StreamTokenizer, tree::TreeBuilder};
+
+/// Parse the html that comes in chunks, like from a file or a socket,
+/// without keeping the whole input in memory.
+///
+/// Tags and chars can be split across the chunks, and invalid UTF-8 is
+/// replaced by `U+FFFD`. Since the chunks are not kept, the nodes own their
+/// names and texts.
+///
+/// ```
+/// use html_query_parser::{Htmlifiable, Parser};
+///
+/// let mut parser = Parser::new();
+/// parser.feed("<p class=\"gr");
+/// parser.feed(b"eeting\">Hello, \xE4\xB8");
+/// parser.feed(b"\x96\xE7\x95\x8C</p>");
+/// let nodes = parser.finish();
+/// assert_eq!(nodes.html(), r#"<p class="greeting">Hello, 世界</p>"#);
+/// ```
+pub struct Parser {
+    tokenizer: StreamTokenizer,
+    tree_builder: TreeBuilder<'static>,
+    // The first bytes of a char that is split across the chunks.
+    incomplete: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser::with_options(&ParseOptions::default())
+    }
+
+    /// Create a parser like [`parse_with`](crate::parse_with) does.
+    pub fn with_options(options: &ParseOptions) -> Self {
+        Parser {
+            tokenizer: StreamTokenizer::new(options.clone()),
+            tree_builder: TreeBuilder::new(options),
+            incomplete: Vec::new(),
+        }
+    }
+
+    /// Feed the next chunk of the html, which can be `&str` or `&[u8]`.
+    pub fn feed<T: AsRef<[u8]>>(&mut self, chunk: T) {
+        let buffer;
+        let mut bytes = chunk.as_ref();
+        if !self.incomplete.is_empty() {
+            buffer = [mem::take(&mut self.incomplete).as_slice(), bytes].concat();
+            bytes = &buffer;
+        }
+        loop {
+            match str::from_utf8(bytes) {
+                Ok(text) => {
+                    self.feed_str(text);
+                    break;
+                }
+                Err(err) => {
+                    let (valid, rest) = bytes.split_at(err.valid_up_to());
+                    self.feed_str(str::from_utf8(valid).unwrap());
+                    match err.error_len() {
+                        Some(len) => {
+                            self.feed_str("\u{FFFD}");
+                            bytes = &rest[len..];
+                        }
+                        // The rest may be completed by the next chunk.
+                        None => {
+                            self.incomplete = rest.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finish parsing and return the dom.
+    pub fn finish(self) -> Vec<Node<'static>> {
+        self.finish_with_errors().0
+    }
+
+    /// Finish parsing like [`finish`](Parser::finish), and also return the
+    /// errors that have been repaired, like
+    /// [`parse_with_errors`](crate::parse_with_errors) does.
+    pub fn finish_with_errors(mut self) -> (Vec<Node<'static>>, Vec<ParseError>) {
+        if !self.incomplete.is_empty() {
+            self.feed_str("\u{FFFD}");
+        }
+        let tree_builder = &mut self.tree_builder;
+        self.tokenizer.finish(&mut |token, span| {
+            tree_builder.push(token.into_owned(), span);
+            tree_builder.in_html()
+        });
+        build(self.tokenizer, self.tree_builder)
+    }
+
+    // Build the dom from the tokens found in `text` as they are found.
+    fn feed_str(&mut self, text: &str) {
+        let tree_builder = &mut self.tree_builder;
+        self.tokenizer.feed(text, &mut |token, span| {
+            tree_builder.push(token.into_owned(), span);
+            tree_builder.in_html()
+        });
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Finish building the dom after all the tokens are pushed, and collect the
+// errors found by the tokenizer and the tree builder.
+pub(crate) fn build<'a>(
+    mut tokenizer: StreamTokenizer,
+    mut tree_builder: TreeBuilder<'a>,
+) -> (Vec<Node<'a>>, Vec<ParseError>) {
+    if tokenizer.stopped() {
+        tree_builder.stop();
+    }
+    let mut errors = tokenizer.take_errors();
+    let (nodes, tree_errors) = tree_builder.finish(tokenizer.location());
+    errors.extend(tree_errors);
+    let mut errors = ParseError::sorted(errors);
+    // Nothing after the error that stops the parsing is reported.
+    let options = tokenizer.options();
+    if let Some(i) = errors
+        .iter()
+        .position(|error| options.stops_at(&error.kind))
+    {
+        errors.truncate(i + 1);
+    }
+    (nodes, errors)
+}
@@ -0,0 +1,97 @@
+use std::ops::Range;
+
+/// Where a token is in the html, with the line and column of its start.
+///
+/// ```
+/// use html_query_parser::Tokenizer;
+///
+/// let html = "<p>\n  <b>Hi</b>\n</p>";
+/// let (_, span) = Tokenizer::new(html).nth(2).unwrap();
+/// assert_eq!(&html[span.range()], "<b>");
+/// assert_eq!((span.line, span.column), (2, 3));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// Byte offset of the start.
+    pub start: usize,
+    /// Byte offset right after the end.
+    pub end: usize,
+    /// Line number of the start, starting from 1.
+    pub line: usize,
+    /// Column number in chars of the start, starting from 1.
+    pub column: usize,
+}
+
+impl Span {
+    pub(crate) fn new(start: Location, end: usize) -> Self {
+        Span {
+            start: start.offset,
+            end,
+            line: start.line,
+            column: start.column,
+        }
+    }
+
+    /// The byte range, which can be used to slice the html.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub(crate) fn location(&self) -> Location {
+        Location {
+            offset: self.start,
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+/// Position in the input, which is tracked while the chars are fed so that
+/// the input does not have to be kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Location {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn start() -> Self {
+        Location {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    // The location after `text`, which starts at `self`.
+    pub fn after(mut self, text: &str) -> Self {
+        // The newlines and chars are counted in one pass, and only text with
+        // newlines is searched for the last line.
+        let bytes = text.as_bytes();
+        self.offset += bytes.len();
+        let (mut newlines, mut chars) = (0, 0);
+        for &byte in bytes {
+            newlines += (byte == b'\n') as usize;
+            chars += is_char_start(byte) as usize;
+        }
+        if newlines == 0 {
+            self.column += chars;
+        } else {
+            let last_line = bytes.iter().rposition(|&byte| byte == b'\n').unwrap();
+            self.line += newlines;
+            self.column = 1 + count_chars(&bytes[last_line + 1..]);
+        }
+        self
+    }
+}
+
+// Check if `byte` starts a char in utf-8, rather than continuing one.
+fn is_char_start(byte: u8) -> bool {
+    (byte as i8) >= -0x40
+}
+
+// Count the chars in utf-8 `bytes` by skipping the continuation bytes.
+fn count_chars(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&byte| is_char_start(byte)).count()
+}
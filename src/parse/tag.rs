@@ -0,0 +1,139 @@
+use std::cmp::Ordering;
+
+// Names of the elements known to the tree builder, which are all those in
+// the tag tables and the common ones, in lowercase and sorted to be binary
+// searched.
+const NAMES: [&str; 166] = [
+    "a", "abbr", "acronym", "address", "annotation-xml", "applet", "area", "article", "aside",
+    "audio", "b", "base", "basefont", "bdi", "bdo", "bgsound", "big", "blockquote", "body", "br",
+    "button", "canvas", "caption", "center", "circle", "cite", "clippath", "code", "col",
+    "colgroup", "data", "datalist", "dd", "defs", "del", "desc", "details", "dfn", "dialog", "dir",
+    "div", "dl", "dt", "ellipse", "em", "embed", "fieldset", "figcaption", "figure", "font",
+    "footer", "foreignobject", "form", "frame", "frameset", "g", "h1", "h2", "h3", "h4", "h5", "h6",
+    "head", "header", "hgroup", "hr", "html", "i", "iframe", "image", "img", "input", "ins", "kbd",
+    "keygen", "label", "legend", "li", "line", "lineargradient", "link", "listing", "main",
+    "malignmark", "map", "mark", "marquee", "mask", "math", "menu", "meta", "meter", "mglyph", "mi",
+    "mn", "mo", "ms", "mtext", "nav", "nobr", "noembed", "noframes", "noscript", "object", "ol",
+    "optgroup", "option", "output", "p", "param", "path", "picture", "plaintext", "polygon",
+    "polyline", "pre", "progress", "q", "radialgradient", "rb", "rect", "rp", "rt", "rtc", "ruby",
+    "s", "samp", "script", "search", "section", "select", "slot", "small", "source", "span",
+    "stop", "strike", "strong", "style", "sub", "summary", "sup", "svg", "symbol", "table",
+    "tbody", "td", "template", "text", "textarea", "tfoot", "th", "thead", "time", "title", "tr",
+    "track", "tspan", "tt", "u", "ul", "use", "var", "video", "wbr", "xmp",
+];
+
+// Compare the lowercase `known` name with `name` in any case.
+fn compare(known: &str, name: &str) -> Ordering {
+    known
+        .bytes()
+        .cmp(name.bytes().map(|byte| byte.to_ascii_lowercase()))
+}
+
+/// An element known to the tree builder, by its index in the names, which
+/// is looked up once per tag so that the tag tables are not searched by
+/// name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag(u8);
+
+impl Tag {
+    /// Find the element named `name` in any case, if it is known.
+    pub fn of(name: &str) -> Option<Tag> {
+        let index = NAMES.binary_search_by(|known| compare(known, name)).ok()?;
+        Some(Tag(index as u8))
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Number of the known elements, which the indices are less than.
+    pub const COUNT: usize = NAMES.len();
+}
+
+// Whether the lowercase names are the same, in a const fn.
+const fn is_same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// A set of known elements, like a tag table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagSet([u64; 3]);
+
+impl TagSet {
+    pub const EMPTY: TagSet = TagSet([0; 3]);
+
+    /// The set of the elements named in lowercase, which fails to compile
+    /// if one of them is not known.
+    pub const fn of(names: &[&str]) -> TagSet {
+        let mut set = [0; 3];
+        let mut i = 0;
+        while i < names.len() {
+            let mut index = 0;
+            while !is_same(NAMES[index], names[i]) {
+                index += 1;
+            }
+            set[index / 64] |= 1 << (index % 64);
+            i += 1;
+        }
+        TagSet(set)
+    }
+
+    pub const fn union(self, other: TagSet) -> TagSet {
+        let (a, b) = (self.0, other.0);
+        TagSet([a[0] | b[0], a[1] | b[1], a[2] | b[2]])
+    }
+
+    pub fn insert(&mut self, tag: Tag) {
+        self.0[tag.index() / 64] |= 1 << (tag.index() % 64);
+    }
+
+    pub fn contains(self, tag: Tag) -> bool {
+        self.0[tag.index() / 64] & 1 << (tag.index() % 64) != 0
+    }
+
+    /// Whether the element is known and in the set.
+    pub fn has(self, tag: Option<Tag>) -> bool {
+        tag.is_some_and(|tag| self.contains(tag))
+    }
+
+    /// The elements in the set.
+    pub fn tags(self) -> impl Iterator<Item = Tag> {
+        (0..3).flat_map(move |i| {
+            let mut bits = self.0[i];
+            std::iter::from_fn(move || {
+                let bit = bits.trailing_zeros();
+                bits &= bits.checked_sub(1)?;
+                Some(Tag((i as u32 * 64 + bit) as u8))
+            })
+        })
+    }
+}
+
+impl From<Tag> for TagSet {
+    fn from(tag: Tag) -> TagSet {
+        let mut set = TagSet::EMPTY;
+        set.insert(tag);
+        set
+    }
+}
+
+// The set of the elements named in lowercase, built at compile time.
+macro_rules! tags {
+    ($($name:literal),*) => {{
+        const TAGS: TagSet = TagSet::of(&[$($name),*]);
+        TAGS
+    }};
+}
+
+pub(crate) use tags;
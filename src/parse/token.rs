@@ -1,97 +1,367 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use crate::Node;
-use crate::parse::attrs;
-
-#[derive(Debug, Clone)]
-pub enum Token {
-    // Like `<div>`, including `<img>`, `<input>`, etc.
-    Start(String, HashMap<String, String>),
-    // Like `</div>`
-    End(String),
-    // Like `<div />`
-    Closing(String, HashMap<String, String>),
-    // Like `<!doctype html>`
-    Doctype,
-    // Like `<!-- comment -->`
-    Comment(String),
-    // Any text
-    Text(String),
+use crate::{is_raw_text, is_void, Namespace, Node};
+use crate::parse::{attrs, ParseErrorKind, ParseOptions};
+
+use super::span::{Location, Span};
+
+/// Token of the html, yielded by [`Tokenizer`](crate::Tokenizer).
+///
+/// The names and texts are borrowed from the html, unless they have to be
+/// changed, like when character references are decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// Like `<div class="app">`, or `<br />` which is self-closing.
+    ///
+    /// The attributes are in the order of the html, without the duplicated
+    /// ones. Their spans are recorded in the same order if enabled by
+    /// [`ParseOptions::spans`], and always by [`Tokenizer`](crate::Tokenizer).
+    StartTag {
+        name: Cow<'a, str>,
+        attrs: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+        self_closing: bool,
+        attr_spans: Vec<Span>,
+    },
+    /// Like `</div>`.
+    EndTag { name: Cow<'a, str> },
+    /// Any text, with the character references decoded if enabled.
+    Text(Cow<'a, str>),
+    /// Like `<!-- comment -->`.
+    Comment(Cow<'a, str>),
+    /// Like `<![CDATA[ text ]]>`.
+    CData(Cow<'a, str>),
+    /// Like `<?xml version="1.0"?>`.
+    ProcessingInstruction {
+        target: Cow<'a, str>,
+        data: Cow<'a, str>,
+    },
+    /// Like `<!DOCTYPE html>`, where the name is in lowercase.
+    Doctype {
+        name: Option<Cow<'a, str>>,
+        public_id: Option<Cow<'a, str>>,
+        system_id: Option<Cow<'a, str>>,
+    },
 }
 
-impl Token {
-    pub fn from(tag: String) -> Option<Self> {
-        if tag.ends_with("/>") {
-            let tag_name_start = tag[1..tag.len()]
-                .chars()
-                .position(|x| x != ' ')
-                .expect("tag name cannot be all spaces after \"<\"")
-                + 1;
-            let tag_name_end_option = tag[tag_name_start..tag.len()]
-                .chars()
-                .position(|x| x == ' ');
-            let tag_name_end = match tag_name_end_option {
-                Some(end) => end + tag_name_start,
-                None => tag.len() - 2,
-            };
-            let tag_name = tag[tag_name_start..tag_name_end].to_string();
-            let attr_str = tag[tag_name_end..tag.len() - 2].trim().to_string();
-            Some(Self::Closing(tag_name, attrs::parse(attr_str)))
-        } else if tag.starts_with("</") {
-            Some(Self::End(tag[2..tag.len() - 1].to_string()))
-        } else if tag.starts_with("<!--") {
-            Some(Self::from_comment(tag))
+fn is_space(ch: char) -> bool {
+    matches!(ch, '\t' | '\n' | '\x0C' | '\r' | ' ')
+}
+
+// Take the quoted doctype identifier at the start of `rest`, and return it
+// with what is left after it.
+fn take_quoted<'a>(
+    rest: &'a str,
+    errors: &mut Vec<ParseErrorKind>,
+) -> (Option<Cow<'a, str>>, &'a str) {
+    let rest = rest.trim_start_matches(is_space);
+    let quote = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => quote,
+        _ => return (None, rest),
+    };
+    match rest[1..].find(quote) {
+        Some(end) => (Some(rest[1..end + 1].into()), &rest[end + 2..]),
+        None => {
+            errors.push(ParseErrorKind::InvalidDoctype);
+            (Some(rest[1..].into()), "")
+        }
+    }
+}
+
+// The length of the tag name at the start of `tag_body`, which ends at a space
+// or `/`.
+fn tag_name_len(tag_body: &str) -> usize {
+    tag_body
+        .bytes()
+        .position(|byte| matches!(byte, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ' | b'/'))
+        .unwrap_or(tag_body.len())
+}
+
+// Check if the tag name starts with an ascii letter, unlike `<1 b>`.
+fn starts_with_letter(tag_name: &str) -> bool {
+    matches!(tag_name.bytes().next(), Some(byte) if byte.is_ascii_alphabetic())
+}
+
+// Create the start tag token of `tag` like `<div class="app">`, or `None` if
+// the tag name does not start with a letter. The spans of the attributes are
+// recorded if `location`, where the tag starts, is given.
+fn start_tag<'a>(
+    tag: &'a str,
+    location: Option<Location>,
+    options: &ParseOptions,
+    errors: &mut Vec<ParseErrorKind>,
+) -> Option<Token<'a>> {
+    let tag_body = &tag[1..tag.len() - 1];
+    let name_end = tag_name_len(tag_body);
+    let name = &tag_body[..name_end];
+    if !starts_with_letter(name) {
+        return None;
+    }
+    let attr_str = &tag_body[name_end..];
+    let attr_location = location
+        .filter(|_| !attr_str.is_empty())
+        .map(|location| location.after(&tag[..1 + name_end]));
+    let (attrs, attr_spans, self_closing) = attrs::parse(attr_str, attr_location, options, errors);
+    Some(Token::StartTag {
+        name: normalize_name(name, options),
+        attrs,
+        self_closing,
+        attr_spans,
+    })
+}
+
+impl<'a> Token<'a> {
+    // Create the token of `tag` like `<div>`, which is sliced out of the input.
+    // Tags that cannot be recognized are pushed to `errors` and kept as text.
+    //
+    // The spans of the attributes are recorded if `location`, where the tag
+    // starts, is given.
+    pub(crate) fn from(
+        tag: &'a str,
+        location: Option<Location>,
+        options: &ParseOptions,
+        errors: &mut Vec<ParseErrorKind>,
+    ) -> Self {
+        let token = if tag.starts_with("</") {
+            // The attributes of end tags are ignored.
+            let tag_body = &tag[2..tag.len() - 1];
+            let tag_name = &tag_body[..tag_name_len(tag_body)];
+            if !starts_with_letter(tag_name) {
+                None
+            } else {
+                Some(Self::EndTag {
+                    name: normalize_name(tag_name, options),
+                })
+            }
         } else if tag.starts_with("<!") {
-            Some(Self::Doctype)
-        } else if tag.starts_with("<") {
-            let tag_name_start = tag[1..tag.len()]
-                .chars()
-                .position(|x| x != ' ')
-                .expect("tag name cannot be all spaces after \"<\"")
-                + 1;
-            let tag_name_end_option = tag[tag_name_start..tag.len()]
-                .chars()
-                .position(|x| x == ' ');
-            let tag_name_end = match tag_name_end_option {
-                Some(end) => end + tag_name_start,
-                None => tag.len() - 1,
-            };
-            let tag_name = tag[tag_name_start..tag_name_end].to_string();
-            let attr_str = tag[tag_name_end..tag.len() - 1].trim().to_string();
-            Some(Self::Start(tag_name, attrs::parse(attr_str)))
+            // Only doctypes are left, as the other markup declarations are
+            // bogus comments.
+            Some(Self::from_doctype(&tag[9..tag.len() - 1], errors))
+        } else if tag.starts_with('<') {
+            start_tag(tag, location, options, errors)
         } else {
             None
+        };
+        token.unwrap_or_else(|| {
+            errors.push(ParseErrorKind::InvalidTag);
+            Self::Text(tag.into())
+        })
+    }
+
+    // Parse the `body` after `<!DOCTYPE`, like ` html PUBLIC "..." "..."`.
+    fn from_doctype(body: &'a str, errors: &mut Vec<ParseErrorKind>) -> Self {
+        let body = body.trim_start_matches(is_space);
+        if body.is_empty() {
+            errors.push(ParseErrorKind::InvalidDoctype);
+            return Self::Doctype {
+                name: None,
+                public_id: None,
+                system_id: None,
+            };
+        }
+        let name_end = body.find(is_space).unwrap_or(body.len());
+        let name = lowercase(&body[..name_end]);
+        let mut rest = body[name_end..].trim_start_matches(is_space);
+        let mut public_id = None;
+        let mut system_id = None;
+        let keyword = rest.get(..6).unwrap_or_default();
+        let has_keyword = if keyword.eq_ignore_ascii_case("public") {
+            (public_id, rest) = take_quoted(&rest[6..], errors);
+            if public_id.is_some() {
+                (system_id, rest) = take_quoted(rest, errors);
+            }
+            true
+        } else if keyword.eq_ignore_ascii_case("system") {
+            (system_id, rest) = take_quoted(&rest[6..], errors);
+            true
+        } else {
+            false
+        };
+        let is_missing_id = has_keyword && public_id.is_none() && system_id.is_none();
+        if is_missing_id || !rest.trim_start_matches(is_space).is_empty() {
+            errors.push(ParseErrorKind::InvalidDoctype);
+        }
+        Self::Doctype {
+            name: Some(name),
+            public_id,
+            system_id,
         }
     }
 
+    // Create the token of a comment, CDATA section or processing instruction,
+    // or of a bogus comment like `<?php>` or `<!ELEMENT br EMPTY>`. The
+    // markup is not closed if the html ends in it.
+    pub(crate) fn from_markup(markup: &'a str, errors: &mut Vec<ParseErrorKind>) -> Self {
+        if let Some(text) = markup.strip_prefix("<![CDATA[") {
+            let text = text.strip_suffix("]]>").unwrap_or_else(|| {
+                errors.push(ParseErrorKind::UnclosedComment);
+                text
+            });
+            return Self::CData(text.into());
+        }
+        if markup.starts_with("<!--") {
+            return Self::from_comment(markup, errors);
+        }
+        if let Some(body) = markup
+            .strip_prefix("<?")
+            .and_then(|body| body.strip_suffix("?>"))
+        {
+            let target_end = body.find(is_space).unwrap_or(body.len());
+            let data = body[target_end..].trim_start_matches(is_space);
+            return Self::ProcessingInstruction {
+                target: body[..target_end].into(),
+                data: data.into(),
+            };
+        }
+        // The `?` of `<?` is kept in the comment, like browsers do.
+        errors.push(ParseErrorKind::BogusComment);
+        let text = &markup[if markup.starts_with("<?") { 1 } else { 2 }..];
+        Self::Comment(text.strip_suffix('>').unwrap_or(text).into())
+    }
+
     #[inline]
-    pub fn from_comment(comment: String) -> Self {
-        Self::Comment(comment[4..comment.len() - 3].to_string())
+    fn from_comment(comment: &'a str, errors: &mut Vec<ParseErrorKind>) -> Self {
+        // `<!-->` and `<!--->` are closed before the comment even starts.
+        if comment == "<!-->" || comment == "<!--->" {
+            errors.push(ParseErrorKind::AbruptComment);
+            return Self::Comment(Cow::Borrowed(""));
+        }
+        let text = &comment[4..];
+        if let Some(text) = text.strip_suffix("-->") {
+            return Self::Comment(text.into());
+        }
+        if let Some(text) = text.strip_suffix("--!>") {
+            errors.push(ParseErrorKind::IncorrectlyClosedComment);
+            return Self::Comment(text.into());
+        }
+        // The dashes that would have closed the comment are not in it.
+        errors.push(ParseErrorKind::UnclosedComment);
+        let text = ["--!", "--", "-"]
+            .into_iter()
+            .find_map(|end| text.strip_suffix(end))
+            .unwrap_or(text);
+        Self::Comment(text.into())
     }
 
-    pub fn node(&self) -> Node {
-        self.clone().into_node()
+    /// Copy the borrowed names and texts, so that the token can outlive
+    /// the html.
+    pub fn into_owned(self) -> Token<'static> {
+        let owned = |text: Cow<str>| Cow::Owned(text.into_owned());
+        match self {
+            Self::StartTag {
+                name,
+                attrs,
+                self_closing,
+                attr_spans,
+            } => Token::StartTag {
+                name: owned(name),
+                attrs: attrs
+                    .into_iter()
+                    .map(|(key, value)| (owned(key), owned(value)))
+                    .collect(),
+                self_closing,
+                attr_spans,
+            },
+            Self::EndTag { name } => Token::EndTag { name: owned(name) },
+            Self::Text(text) => Token::Text(owned(text)),
+            Self::Comment(comment) => Token::Comment(owned(comment)),
+            Self::CData(text) => Token::CData(owned(text)),
+            Self::ProcessingInstruction { target, data } => Token::ProcessingInstruction {
+                target: owned(target),
+                data: owned(data),
+            },
+            Self::Doctype {
+                name,
+                public_id,
+                system_id,
+            } => Token::Doctype {
+                name: name.map(owned),
+                public_id: public_id.map(owned),
+                system_id: system_id.map(owned),
+            },
+        }
     }
 
-    pub fn into_node(self) -> Node {
+    pub(crate) fn into_node(self, span: Option<Span>) -> Node<'a> {
         match self {
-            Self::Start(name, attrs) => Node::Element {
+            Self::StartTag {
                 name,
                 attrs,
+                attr_spans,
+                ..
+            } => Node::Element {
+                void: is_void(&name),
+                raw_text: is_raw_text(&name),
+                name,
+                attr_spans: attr_map(&attrs, attr_spans),
+                attrs: attrs.into_iter().collect(),
                 children: Vec::new(),
+                span,
+                namespace: Namespace::Html,
+                content: None,
+                raw_attrs: false,
             },
-            Self::End(name) => Node::Element {
+            Self::EndTag { name } => Node::Element {
+                void: is_void(&name),
+                raw_text: is_raw_text(&name),
                 name,
                 attrs: HashMap::new(),
                 children: Vec::new(),
+                span,
+                attr_spans: HashMap::new(),
+                namespace: Namespace::Html,
+                content: None,
+                raw_attrs: false,
+            },
+            Self::Text(text) => Node::Text {
+                text,
+                span,
+                raw: false,
             },
-            Self::Closing(name, attrs) => Node::Element {
+            Self::Comment(comment) => Node::Comment(comment, span),
+            Self::CData(text) => Node::CData(text, span),
+            Self::ProcessingInstruction { target, data } => {
+                Node::ProcessingInstruction { target, data, span }
+            }
+            Self::Doctype {
                 name,
-                attrs,
-                children: Vec::new(),
+                public_id,
+                system_id,
+            } => Node::Doctype {
+                name,
+                public_id,
+                system_id,
+                span,
             },
-            Self::Doctype => Node::Doctype,
-            Self::Comment(comment) => Node::Comment(comment),
-            Self::Text(text) => Node::Text(text),
         }
     }
 }
+
+// The spans of the attributes by their keys, which are in the same order.
+pub(crate) fn attr_map<'a>(
+    attrs: &[(Cow<'a, str>, Cow<'a, str>)],
+    attr_spans: Vec<Span>,
+) -> HashMap<Cow<'a, str>, Span> {
+    attrs
+        .iter()
+        .map(|(key, _)| key.clone())
+        .zip(attr_spans)
+        .collect()
+}
+
+// The name of a tag or attribute, in lowercase if enabled.
+fn normalize_name<'a>(name: &'a str, options: &ParseOptions) -> Cow<'a, str> {
+    if options.lowercase_names {
+        lowercase(name)
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+// Lowercase `name`, which is only copied if it has uppercase letters.
+fn lowercase(name: &str) -> Cow<'_, str> {
+    if name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
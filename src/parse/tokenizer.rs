@@ -0,0 +1,609 @@
+use std::mem;
+
+use crate::{
+    data::RCDATA_TAGS,
+    parse::{entities, ParseError, ParseErrorKind, ParseOptions},
+};
+
+use super::{
+    attrs::TagState,
+    limits,
+    span::{Location, Span},
+    token::Token,
+};
+
+/// Split the html into [`Token`]s with their [`Span`]s, without building
+/// the dom.
+///
+/// The tokens are sliced straight out of the html. Like
+/// [`parse`](crate::parse), the content of elements like `script` and
+/// `title` is a single text token, and malformed tags are kept as text.
+///
+/// ```
+/// use html_query_parser::{Token, Tokenizer};
+///
+/// let html = r#"<a href="/a">A</a><script>"<a>"</script><a href="/b">B</a>"#;
+/// let links: Vec<_> = Tokenizer::new(html)
+///     .filter_map(|(token, span)| match token {
+///         Token::StartTag { name, attrs, .. } if name == "a" => Some((attrs, span)),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(links.len(), 2);
+/// assert_eq!(links[1].0, [("href".into(), "/b".into())]);
+/// assert_eq!(&html[links[1].1.range()], r#"<a href="/b">"#);
+/// ```
+pub struct Tokenizer<'a> {
+    html: &'a str,
+    // Byte offset of the html that has not been tokenized.
+    pos: usize,
+    tokenizer: StreamTokenizer,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(html: &'a str) -> Self {
+        Tokenizer::with_options(html, &ParseOptions::default())
+    }
+
+    /// Create a tokenizer like [`parse_with`](crate::parse_with) does,
+    /// except that the spans of attributes are always recorded.
+    pub fn with_options(html: &'a str, options: &ParseOptions) -> Self {
+        let options = ParseOptions {
+            spans: true,
+            ..options.clone()
+        };
+        Tokenizer {
+            html,
+            pos: 0,
+            tokenizer: StreamTokenizer::new(options),
+        }
+    }
+
+    /// Take the errors found in the tokens that have been yielded, sorted
+    /// by their positions.
+    pub fn take_errors(&mut self) -> Vec<ParseError> {
+        ParseError::sorted(self.tokenizer.take_errors())
+    }
+
+    // Start over to tokenize `html` instead, which saves creating another
+    // tokenizer with the same options.
+    pub(crate) fn restart(&mut self, html: &'a str) {
+        self.html = html;
+        self.pos = 0;
+        self.tokenizer.restart();
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = (Token<'a>, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Each scan emits one token at most, so none has to be queued.
+        loop {
+            let mut token = None;
+            let mut emit = |next, span| {
+                token = Some((next, span));
+                true
+            };
+            // Everything is taken at once at the end of the html.
+            let html = &self.html[self.pos..];
+            self.pos += self.tokenizer.scan_token(html, true, &mut emit)?;
+            if token.is_some() {
+                return token;
+            }
+        }
+    }
+}
+
+/// Split the html into tokens as its chunks are fed, so that a tag can be
+/// split across the chunks of the input.
+///
+/// The html is scanned as bytes, since all the delimiters are ascii, and the
+/// tokens borrowing from it are passed to a callback as soon as they are
+/// found. The callback returns whether the current node is html after the
+/// token, or svg or math otherwise, where the content of elements like
+/// `style` and `title` is not text but tokenized as usual.
+pub(crate) struct StreamTokenizer {
+    options: ParseOptions,
+    // The input that cannot be tokenized till more of it is fed.
+    buffer: String,
+    // Location of the first byte that has not been tokenized.
+    location: Location,
+    // How far the current token has been scanned, and the state of the tag
+    // that it ends in, so that the scanning can resume when more input is
+    // fed.
+    scanned: usize,
+    tag_state: TagState,
+    // The content of elements like `script` and `title` is text till the end
+    // tag with this name, or till the end of the html if the name is empty,
+    // where only the character references in the RCDATA of `title` and
+    // `textarea` are decoded if the bool is true.
+    text_end: Option<(String, bool)>,
+    errors: Vec<(ParseErrorKind, Location)>,
+    token_errors: Vec<ParseErrorKind>,
+    // Whether it stops at an error, see `ParseOptions::stops_at`.
+    stopped: bool,
+    // Whether the input is cut off at `max_input_len`, where the tag cut off
+    // is not reported, since the input is.
+    cut_off: bool,
+}
+
+// The start and end of comments, CDATA sections and processing instructions,
+// where quotes are not special. Processing instructions end at the first `>`
+// like bogus comments, which they are in the html spec.
+const MARKUP: [(&str, &str); 3] = [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", ">")];
+
+impl StreamTokenizer {
+    pub fn new(options: ParseOptions) -> Self {
+        StreamTokenizer {
+            options,
+            buffer: String::new(),
+            location: Location::start(),
+            scanned: 0,
+            tag_state: TagState::TagName,
+            text_end: None,
+            errors: Vec::new(),
+            token_errors: Vec::new(),
+            stopped: false,
+            cut_off: false,
+        }
+    }
+
+    // Reset the state to tokenize another html with the same options.
+    pub fn restart(&mut self) {
+        self.buffer.clear();
+        self.location = Location::start();
+        self.scanned = 0;
+        self.tag_state = TagState::TagName;
+        self.text_end = None;
+        self.errors.clear();
+        self.token_errors.clear();
+        self.stopped = false;
+        self.cut_off = false;
+    }
+
+    /// Tokenize the html as the content of the element `context`, where the
+    /// content of elements like `textarea` is text till the end of the
+    /// html, since it is not inside a `textarea` start tag to be closed.
+    pub fn start_in(&mut self, context: &str) {
+        let is_tag = |tag: &str| tag.eq_ignore_ascii_case(context);
+        if self.options.raw_text_tags.iter().any(|tag| is_tag(tag)) || is_tag("plaintext") {
+            self.text_end = Some((String::new(), false));
+        } else if RCDATA_TAGS.iter().any(|tag| is_tag(tag)) {
+            self.text_end = Some((String::new(), self.options.decode_entities));
+        }
+    }
+
+    /// Feed the next chunk of the html, and `emit` the tokens found.
+    pub fn feed(&mut self, text: &str, emit: &mut impl FnMut(Token, Span) -> bool) {
+        // Only what cannot be tokenized yet is copied.
+        if self.stopped() {
+            return;
+        }
+        if self.buffer.is_empty() {
+            let len = self.scan(text, false, emit);
+            self.buffer.push_str(&text[len..]);
+            return;
+        }
+        let mut buffer = mem::take(&mut self.buffer);
+        buffer.push_str(text);
+        let len = self.scan(&buffer, false, emit);
+        buffer.drain(..len);
+        self.buffer = buffer;
+    }
+
+    /// Flush the text left at the end of the html. Unclosed tags are dropped
+    /// with an error.
+    pub fn finish(&mut self, emit: &mut impl FnMut(Token, Span) -> bool) {
+        let buffer = mem::take(&mut self.buffer);
+        self.scan(&buffer, true, emit);
+    }
+
+    /// Tokenize the whole html at once, where the tokens borrow from it.
+    pub fn tokenize<'a>(&mut self, html: &'a str, emit: &mut impl FnMut(Token<'a>, Span) -> bool) {
+        self.scan(html, true, emit);
+    }
+
+    /// Where the html that has been tokenized ends.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    /// Take the errors found so far.
+    pub fn take_errors(&mut self) -> Vec<(ParseErrorKind, Location)> {
+        mem::take(&mut self.errors)
+    }
+
+    // Tokenize `input` as far as possible, and return how many bytes of it
+    // have been tokenized.
+    fn scan<'a>(
+        &mut self,
+        input: &'a str,
+        eof: bool,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> usize {
+        let mut pos = 0;
+        while let Some(len) = self.scan_token(&input[pos..], eof, emit) {
+            pos += len;
+        }
+        pos
+    }
+
+    // Emit the token at the start of `input`, and the text before it if any,
+    // then return how many bytes have been tokenized.
+    //
+    // `None` is returned if more input is needed, unless it is the end of
+    // the html (`eof`) where everything left is taken.
+    fn scan_token<'a>(
+        &mut self,
+        input: &'a str,
+        eof: bool,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> Option<usize> {
+        if input.is_empty() || self.stopped() {
+            return None;
+        }
+        // The input over the limit is dropped as if the html ended before it.
+        let max_len = self.options.limits.max_input_len - self.location.offset;
+        let (input, eof) = match limits::floor_char_boundary(input, max_len) {
+            0 if max_len < input.len() => {
+                self.report(ParseErrorKind::InputTooLarge, self.location);
+                return None;
+            }
+            len if len < input.len() => {
+                self.cut_off = true;
+                (&input[..len], true)
+            }
+            _ => (input, eof),
+        };
+        if self.text_end.is_some() {
+            return self.scan_raw_text(input, eof, emit);
+        }
+        let bytes = input.as_bytes();
+        if bytes[0] != b'<' || !bytes.get(1).is_some_and(|&byte| starts_tag(byte)) {
+            return self.scan_text(input, eof, emit);
+        }
+        if !matches!(bytes[1], b'!' | b'?') {
+            return self.scan_tag(input, eof, emit);
+        }
+        for (start, end) in MARKUP {
+            if input.starts_with(start) {
+                return self.scan_markup(input, end, eof, emit);
+            }
+            // Wait to see if it is the markup.
+            if !eof && start.starts_with(input) {
+                return None;
+            }
+        }
+        // Markup declarations other than doctype are bogus comments.
+        if let Some(rest) = input.strip_prefix("<!") {
+            let len = rest.len().min(7);
+            let is_doctype = rest.as_bytes()[..len].eq_ignore_ascii_case(&b"doctype"[..len]);
+            if is_doctype && len < 7 && !eof {
+                return None;
+            }
+            if !is_doctype || len < 7 {
+                return self.scan_markup(input, ">", eof, emit);
+            }
+        }
+        self.scan_tag(input, eof, emit)
+    }
+
+    // Scan the text at the start of `input` till the next `<` that starts a
+    // tag. Other `<`, like in `1 < 2`, are kept in the text.
+    fn scan_text<'a>(
+        &mut self,
+        input: &'a str,
+        eof: bool,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> Option<usize> {
+        let bytes = input.as_bytes();
+        let mut from = self.scanned;
+        // The `<` kept in the text are located from one to the next.
+        let mut location = self.location;
+        let mut located = 0;
+        let end = loop {
+            let next = bytes[from..].iter().position(|&byte| byte == b'<');
+            let Some(start) = next.map(|i| from + i) else {
+                if eof {
+                    break input.len();
+                }
+                self.scanned = input.len();
+                return None;
+            };
+            match bytes.get(start + 1) {
+                Some(&byte) if starts_tag(byte) => break start,
+                Some(_) => {
+                    location = location.after(&input[located..start]);
+                    located = start;
+                    self.report(ParseErrorKind::InvalidTag, location);
+                    from = start + 1;
+                }
+                None if eof => {
+                    location = location.after(&input[located..start]);
+                    self.report(ParseErrorKind::EofInTag, location);
+                    break input.len();
+                }
+                // Wait to see if `<` at the end starts a tag.
+                None => {
+                    self.scanned = start;
+                    return None;
+                }
+            }
+        };
+        self.push_text(&input[..end], self.options.decode_entities, emit);
+        Some(self.consume(input, end))
+    }
+
+    // Scan the tag like `<div class="app">` at the start of `input`.
+    fn scan_tag<'a>(
+        &mut self,
+        input: &'a str,
+        eof: bool,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> Option<usize> {
+        let bytes = input.as_bytes();
+        let mut state = self.tag_state;
+        let mut i = self.scanned.max(1);
+        // The state only matters to quotes, so a tag without them ends at
+        // the first `>`, or at `<` if it is not closed.
+        if !matches!(state, TagState::Value(Some(_))) {
+            let next = bytes[i..]
+                .iter()
+                .position(|&byte| matches!(byte, b'>' | b'<' | b'"' | b'\''));
+            match next.map(|len| (i + len, bytes[i + len])) {
+                Some((end, b'>')) => return Some(self.push_tag(input, end + 1, emit)),
+                Some((end, b'<')) => {
+                    self.push_text(&input[..end], self.options.decode_entities, emit);
+                    return Some(self.consume(input, end));
+                }
+                _ => {}
+            }
+        }
+        while i < bytes.len() {
+            // Quoted values are skipped at once, as only the quote ends them.
+            if let TagState::Value(Some(quote)) = state {
+                match bytes[i..].iter().position(|&byte| byte == quote) {
+                    Some(len) => i += len,
+                    None => break,
+                }
+            }
+            let byte = bytes[i];
+            match state {
+                TagState::Value(Some(_)) => {}
+                _ if byte == b'>' => return Some(self.push_tag(input, i + 1, emit)),
+                // The tag is not closed, so it is kept as text.
+                _ if byte == b'<' => {
+                    self.push_text(&input[..i], self.options.decode_entities, emit);
+                    return Some(self.consume(input, i));
+                }
+                _ => {}
+            }
+            state = state.next(byte);
+            i += 1;
+        }
+        if eof {
+            if !self.cut_off {
+                self.report(ParseErrorKind::EofInTag, self.location);
+            }
+            return Some(self.consume(input, input.len()));
+        }
+        self.scanned = input.len();
+        self.tag_state = state;
+        None
+    }
+
+    // Scan the markup at the start of `input` till `end`, or till the end of
+    // the html if it is not closed.
+    fn scan_markup<'a>(
+        &mut self,
+        input: &'a str,
+        end: &str,
+        eof: bool,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> Option<usize> {
+        // Search after `<!` or `<?`, so that `<!-->` is closed by itself.
+        let from = self.scanned.max(2);
+        match markup_end(input, from, end) {
+            Some(len) => {
+                let token = Token::from_markup(&input[..len], &mut self.token_errors);
+                self.push_token(token, len, emit);
+                Some(self.consume(input, len))
+            }
+            None if eof => {
+                let token = Token::from_markup(input, &mut self.token_errors);
+                self.push_token(token, input.len(), emit);
+                Some(self.consume(input, input.len()))
+            }
+            None => {
+                // The end, which is at most `--!>`, may be split across the
+                // chunks.
+                let mut scanned = input.len().saturating_sub(3).max(from);
+                while !input.is_char_boundary(scanned) {
+                    scanned -= 1;
+                }
+                self.scanned = scanned;
+                None
+            }
+        }
+    }
+
+    // Scan the content of elements like `script` till its end tag.
+    fn scan_raw_text<'a>(
+        &mut self,
+        input: &'a str,
+        eof: bool,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> Option<usize> {
+        let (name, decode) = self.text_end.take().unwrap();
+        let bytes = input.as_bytes();
+        let tag_len = name.len() + 2;
+        let mut from = self.scanned;
+        while let Some(i) = input[from..].find("</").filter(|_| !name.is_empty()) {
+            let start = from + i;
+            if start + tag_len >= bytes.len() {
+                if eof {
+                    break;
+                }
+                self.scanned = start;
+                self.text_end = Some((name, decode));
+                return None;
+            }
+            if bytes[start + 2..start + tag_len].eq_ignore_ascii_case(name.as_bytes())
+                && matches!(
+                    bytes[start + tag_len],
+                    b'\t' | b'\n' | b'\x0C' | b'\r' | b' ' | b'/' | b'>'
+                )
+            {
+                // The end tag is then tokenized as usual.
+                if start > 0 {
+                    self.push_text(&input[..start], decode, emit);
+                }
+                return Some(self.consume(input, start));
+            }
+            from = start + 1;
+        }
+        if eof {
+            self.push_text(input, decode, emit);
+            return Some(self.consume(input, input.len()));
+        }
+        // `<` at the end may start the end tag.
+        self.scanned = input.len() - input.ends_with('<') as usize;
+        self.text_end = Some((name, decode));
+        None
+    }
+
+    // Move over the `len` bytes of `input` that have been tokenized.
+    fn consume(&mut self, input: &str, len: usize) -> usize {
+        self.location = self.location.after(&input[..len]);
+        self.scanned = 0;
+        self.tag_state = TagState::TagName;
+        len
+    }
+
+    // Emit the tag of `len` bytes at the start of `input`.
+    fn push_tag<'a>(
+        &mut self,
+        input: &'a str,
+        len: usize,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> usize {
+        let tag = &input[..len];
+        let location = self.options.spans.then_some(self.location);
+        let token = Token::from(tag, location, &self.options, &mut self.token_errors);
+        let mut text_end = None;
+        if let Token::StartTag {
+            name,
+            self_closing: false,
+            ..
+        } = &token
+        {
+            let is_tag = |tag: &str| tag.eq_ignore_ascii_case(name);
+            if self.options.raw_text_tags.iter().any(|tag| is_tag(tag)) {
+                text_end = Some((name.to_ascii_lowercase(), false));
+            } else if RCDATA_TAGS.iter().any(|tag| is_tag(tag)) {
+                let decode = self.options.decode_entities;
+                text_end = Some((name.to_ascii_lowercase(), decode));
+            }
+        }
+        // Elements like `style` in svg or math have no raw text.
+        if self.push_token(token, len, emit) {
+            self.text_end = text_end;
+        }
+        self.consume(input, len)
+    }
+
+    // Emit the token of `len` bytes that starts at `location`, with the
+    // errors found while creating it, and return whether the current node is
+    // html after it, as `emit` does.
+    fn push_token<'a>(
+        &mut self,
+        mut token: Token<'a>,
+        len: usize,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) -> bool {
+        if let Token::Comment(text) | Token::CData(text) = &mut token {
+            if limits::truncate(text, self.options.limits.max_text_len) {
+                self.token_errors.push(ParseErrorKind::TextTooLong);
+            }
+        }
+        let location = self.location;
+        if !self.token_errors.is_empty() {
+            let options = &self.options;
+            self.stopped |= self.token_errors.iter().any(|kind| options.stops_at(kind));
+            self.errors
+                .extend(self.token_errors.drain(..).map(|kind| (kind, location)));
+        }
+        !self.stopped() && emit(token, Span::new(location, location.offset + len))
+    }
+
+    // Emit the text that starts at `location`, and decode its character
+    // references if `decode` is true.
+    fn push_text<'a>(
+        &mut self,
+        text: &'a str,
+        decode: bool,
+        emit: &mut impl FnMut(Token<'a>, Span) -> bool,
+    ) {
+        let location = self.location;
+        let span = Span::new(location, location.offset + text.len());
+        let mut text = if decode {
+            let mut reference_errors = Vec::new();
+            let decoded = entities::decode(text, false, &mut reference_errors);
+            let mut error_location = location;
+            let mut scanned = 0;
+            for i in reference_errors {
+                error_location = error_location.after(&text[scanned..i]);
+                scanned = i;
+                self.report(ParseErrorKind::InvalidCharacterReference, error_location);
+            }
+            decoded
+        } else {
+            text.into()
+        };
+        if limits::truncate(&mut text, self.options.limits.max_text_len) {
+            self.report(ParseErrorKind::TextTooLong, location);
+        }
+        if !self.stopped() {
+            emit(Token::Text(text), span);
+        }
+    }
+
+    // Record the error found at `location`.
+    fn report(&mut self, kind: ParseErrorKind, location: Location) {
+        self.stopped |= self.options.stops_at(&kind);
+        self.errors.push((kind, location));
+    }
+
+    /// Whether it stops at an error, like in the strict mode, where the
+    /// token with it is not emitted.
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+}
+
+// Check if `<` followed by `byte` starts a tag or markup, or is kept in the
+// text otherwise.
+fn starts_tag(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || matches!(byte, b'/' | b'!' | b'?')
+}
+
+// Find the length of the markup at the start of `input` closed by `end`,
+// which is searched for after `from` bytes. Comments can also be closed by
+// `--!>`, though it is an error.
+fn markup_end(input: &str, from: usize, end: &str) -> Option<usize> {
+    let len = input[from..].find(end).map(|i| from + i + end.len());
+    if end != "-->" {
+        return len;
+    }
+    // `--!>` cannot overlap `<!--`, unlike `-->` in `<!-->`.
+    let from = from.max(4);
+    input[from..len.unwrap_or(input.len())]
+        .find("--!>")
+        .map(|i| from + i + 4)
+        .or(len)
+}
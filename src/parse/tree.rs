@@ -0,0 +1,942 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    data::{
+        BREAKOUT_TAGS, CLOSING_P_TAGS, HEADING_TAGS, HEAD_TAGS, MATHML_HTML_TAGS,
+        OPTIONAL_END_TAGS, SCOPE_TAGS, SPECIAL_TAGS, SVG_HTML_TAGS, TABLE_CONTEXT_TAGS, TABLE_TAGS,
+    },
+    parse::{LimitPolicy, Limits, ParseErrorKind, ParseOptions},
+    Namespace, Node,
+};
+
+use super::{
+    span::{Location, Span},
+    tag::{tags, Tag, TagSet},
+    token::{attr_map, Token},
+};
+
+const SCOPE: TagSet = TagSet::of(&SCOPE_TAGS);
+const SPECIAL: TagSet = TagSet::of(&SPECIAL_TAGS);
+const SVG_HTML: TagSet = TagSet::of(&SVG_HTML_TAGS);
+const MATHML_HTML: TagSet = TagSet::of(&MATHML_HTML_TAGS);
+const TABLE: TagSet = TagSet::of(&TABLE_TAGS);
+const TABLE_CONTEXT: TagSet = TagSet::of(&TABLE_CONTEXT_TAGS);
+const HEAD: TagSet = TagSet::of(&HEAD_TAGS);
+const HEADING: TagSet = TagSet::of(&HEADING_TAGS);
+const CLOSING_P: TagSet = TagSet::of(&CLOSING_P_TAGS);
+const OPTIONAL_END: TagSet = TagSet::of(&OPTIONAL_END_TAGS);
+const BREAKOUT: TagSet = TagSet::of(&BREAKOUT_TAGS);
+
+// An element whose end tag has not been reached yet.
+struct OpenElement<'a> {
+    name: Cow<'a, str>,
+    tag: Option<Tag>,
+    attrs: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    children: Vec<Node<'a>>,
+    attr_spans: HashMap<Cow<'a, str>, Span>,
+    namespace: Namespace,
+    // Whether it is misplaced in a table, so that it is moved before the
+    // table when closed.
+    fostered: bool,
+    // Where the start tag is.
+    span: Span,
+    // Index of the open element below it with the same name, which is the
+    // last one again when it is closed.
+    previous: Option<usize>,
+}
+
+// Attribute names and values are compared case-insensitively.
+fn is_one_of(name: &str, names: &[&str]) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+// The name in lowercase, which is only copied if it is not already.
+fn lowercase(name: &str) -> Cow<'_, str> {
+    if name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+// Whether the element is `<template>`, whose children are kept as its
+// content instead.
+fn is_template(tag: Option<Tag>, namespace: Namespace) -> bool {
+    namespace == Namespace::Html && tags!["template"].has(tag)
+}
+
+// The namespace of the element outside of svg and math.
+fn namespace_of(tag: Option<Tag>) -> Namespace {
+    if tags!["svg"].has(tag) {
+        Namespace::Svg
+    } else if tags!["math"].has(tag) {
+        Namespace::MathMl
+    } else {
+        Namespace::Html
+    }
+}
+
+// Whether the start tag can only be html, which closes the open svg and
+// math elements.
+fn is_breakout(tag: Option<Tag>, attrs: &[(Cow<'_, str>, Cow<'_, str>)]) -> bool {
+    BREAKOUT.has(tag)
+        || tags!["font"].has(tag)
+            && attrs
+                .iter()
+                .any(|(key, _)| is_one_of(key, &["color", "face", "size"]))
+}
+
+// The known elements in `names`, and the other names, which are looked up
+// by name.
+fn split_names(names: &[String]) -> (TagSet, Vec<String>) {
+    let mut tags = TagSet::EMPTY;
+    let mut others = Vec::new();
+    for name in names {
+        match Tag::of(name) {
+            Some(tag) => tags.insert(tag),
+            None => others.push(name.clone()),
+        }
+    }
+    (tags, others)
+}
+
+// Whether the element is in the known elements `tags` or named in `others`.
+fn is_in(name: &str, tag: Option<Tag>, (tags, others): &(TagSet, Vec<String>)) -> bool {
+    match tag {
+        Some(tag) => tags.contains(tag),
+        None => others.iter().any(|other| other.eq_ignore_ascii_case(name)),
+    }
+}
+
+/// Build the dom from tokens in a single pass with a stack of the open
+/// elements, repairing mismatched and unclosed tags like browsers do.
+pub struct TreeBuilder<'a> {
+    nodes: Vec<Node<'a>>,
+    open_elements: Vec<OpenElement<'a>>,
+    errors: Vec<(ParseErrorKind, Location)>,
+    // Whether to record the spans of the nodes.
+    spans: bool,
+    keep_comments: bool,
+    keep_whitespace: bool,
+    // Whether the character references are decoded, or the text and the
+    // attribute values are raw otherwise.
+    decode_entities: bool,
+    void_tags: (TagSet, Vec<String>),
+    raw_text_tags: (TagSet, Vec<String>),
+    strict: bool,
+    limits: Limits,
+    // Whether to ignore the rest of the tokens after an error in the strict
+    // mode, or after a limit is exceeded.
+    stopped: bool,
+    // Number of the nodes created.
+    node_count: usize,
+    // Names of the elements dropped for being nested too deep, so that their
+    // end tags are ignored.
+    too_deep: Vec<Cow<'a, str>>,
+    // Number of the open elements at the bottom of the stack that the html
+    // cannot close, which is the context element of a fragment.
+    base: usize,
+    // Whether to build a whole document, where the `html`, `head` and
+    // `body` elements are implied, and whether the `head` and `body` have
+    // been created.
+    document: bool,
+    // Whether the `tbody`, `tr`, etc. implied in tables are opened, and the
+    // content misplaced in them moved, which is done in a document or in a
+    // fragment inside a table.
+    tables: bool,
+    has_head: bool,
+    has_body: bool,
+    // Indices of the last open elements of the known tags, and of the
+    // others by their lowercase names, which link to the previous ones of
+    // the same names, and of the open scope and special elements, so that
+    // finding an open element in scope does not walk through the stack,
+    // which would take quadratic time in deeply nested html.
+    last_open_tags: Vec<Option<usize>>,
+    last_open_names: HashMap<Cow<'a, str>, usize>,
+    scope_indices: Vec<usize>,
+    special_indices: Vec<usize>,
+    // Where the elements closed now end, which is the end of the last token
+    // by default.
+    end: usize,
+}
+
+impl<'a> TreeBuilder<'a> {
+    pub fn new(options: &ParseOptions) -> Self {
+        TreeBuilder {
+            nodes: Vec::new(),
+            open_elements: Vec::new(),
+            errors: Vec::new(),
+            spans: options.spans,
+            keep_comments: options.keep_comments,
+            keep_whitespace: options.keep_whitespace,
+            decode_entities: options.decode_entities,
+            void_tags: split_names(&options.void_tags),
+            raw_text_tags: split_names(&options.raw_text_tags),
+            strict: options.strict,
+            limits: options.limits,
+            stopped: false,
+            node_count: 0,
+            too_deep: Vec::new(),
+            base: 0,
+            document: false,
+            tables: false,
+            has_head: false,
+            has_body: false,
+            last_open_tags: vec![None; Tag::COUNT],
+            last_open_names: HashMap::new(),
+            scope_indices: Vec::new(),
+            special_indices: Vec::new(),
+            end: 0,
+        }
+    }
+
+    /// Create a tree builder for a fragment of the html inside the element
+    /// `context`, which is not returned itself. Like the `html` element,
+    /// it cannot be closed, and the end tags and the implied end tags do
+    /// not reach past it.
+    pub fn with_context(options: &ParseOptions, context: &str) -> Self {
+        let mut tree_builder = TreeBuilder::new(options);
+        let tag = Tag::of(context);
+        let namespace = namespace_of(tag);
+        tree_builder.open(OpenElement {
+            name: namespace.adjust_tag_name(Cow::Owned(context.to_string())),
+            tag,
+            attrs: HashMap::new(),
+            children: Vec::new(),
+            attr_spans: HashMap::new(),
+            namespace,
+            fostered: false,
+            span: Span::new(Location::start(), 0),
+            previous: None,
+        });
+        tree_builder.base = 1;
+        tree_builder.tables = TABLE_CONTEXT.has(tag);
+        tree_builder
+    }
+
+    /// Create a tree builder for a whole document, which creates the
+    /// `html`, `head` and `body` elements implied by the html, and moves
+    /// the misplaced content to where it belongs like browsers do.
+    pub fn for_document(options: &ParseOptions) -> Self {
+        TreeBuilder {
+            document: true,
+            tables: true,
+            ..TreeBuilder::new(options)
+        }
+    }
+
+    /// Process a token at `span` of the html.
+    pub fn push(&mut self, token: Token<'a>, span: Span) {
+        if self.stopped {
+            return;
+        }
+        match token {
+            Token::EndTag { name } => self.end(name, span),
+            Token::Text(text) if !self.keep_whitespace && text.trim().is_empty() => {}
+            Token::Comment(_) if !self.keep_comments => {}
+            // The rest of the html is dropped.
+            _ if self.node_count == self.limits.max_nodes => {
+                self.errors
+                    .push((ParseErrorKind::TooManyNodes, span.location()));
+                self.stopped = true;
+            }
+            token => {
+                self.node_count += 1;
+                self.push_node(token, span);
+            }
+        }
+        self.end = span.end;
+        self.stopped |= self.strict && !self.errors.is_empty();
+    }
+
+    // Process the token that creates a node.
+    fn push_node(&mut self, token: Token<'a>, span: Span) {
+        let tag = match &token {
+            Token::StartTag { name, .. } => Tag::of(name),
+            _ => None,
+        };
+        if self.document && !self.push_document(&token, tag, span) {
+            return;
+        }
+        match token {
+            Token::StartTag {
+                mut name,
+                mut attrs,
+                self_closing,
+                attr_spans,
+            } => {
+                let mut foreign = self.in_foreign_content(tag);
+                if foreign && is_breakout(tag, &attrs) {
+                    self.close_foreign_content(tag);
+                    foreign = false;
+                }
+                let mut fostered = false;
+                let namespace = match self.open_elements.last() {
+                    Some(current) if foreign => current.namespace,
+                    _ => {
+                        self.close_implied_by(tag);
+                        if self.tables {
+                            fostered = self.push_table(tag, &attrs, span);
+                        }
+                        namespace_of(tag)
+                    }
+                };
+                if namespace != Namespace::Html {
+                    name = namespace.adjust_tag_name(name);
+                    attrs = attrs
+                        .into_iter()
+                        .map(|(key, value)| (namespace.adjust_attr_name(key), value))
+                        .collect();
+                }
+                let attr_spans = attr_map(&attrs, attr_spans);
+                let attrs: HashMap<_, _> = attrs.into_iter().collect();
+                // Only html has void elements, while svg and math elements
+                // can be self-closing.
+                let void = namespace == Namespace::Html && is_in(&name, tag, &self.void_tags);
+                let is_void = self_closing || void;
+                if self.open_elements.len() >= self.limits.max_depth {
+                    // Only the outermost element dropped is reported.
+                    if self.too_deep.is_empty() {
+                        self.errors.push((ParseErrorKind::TooDeep, span.location()));
+                        self.stopped = self.limits.policy == LimitPolicy::Error;
+                    }
+                    if !is_void {
+                        self.too_deep.push(name);
+                    }
+                } else if is_void {
+                    let content = is_template(tag, namespace).then(Vec::new);
+                    let node = Node::Element {
+                        raw_text: self.is_raw_text(&name, tag, namespace),
+                        name,
+                        attrs,
+                        children: Vec::new(),
+                        span: self.spans.then_some(span),
+                        attr_spans,
+                        namespace,
+                        content,
+                        raw_attrs: !self.decode_entities,
+                        void,
+                    };
+                    if fostered {
+                        self.foster(node);
+                    } else {
+                        self.insert(node);
+                    }
+                } else {
+                    self.open(OpenElement {
+                        name,
+                        tag,
+                        attrs,
+                        children: Vec::new(),
+                        attr_spans,
+                        namespace,
+                        fostered,
+                        span,
+                        previous: None,
+                    });
+                }
+            }
+            Token::Text(text) => {
+                // Text in `head` belongs to `body` instead.
+                if !text.trim().is_empty() && self.current_is(tags!["head"]) {
+                    self.close_to(self.open_elements.len() - 1);
+                }
+                let misplaced = self.tables
+                    && !text.trim().is_empty()
+                    && self.current_or_context_is(TABLE_CONTEXT);
+                let node = Node::Text {
+                    text,
+                    span: self.spans.then_some(span),
+                    raw: !self.decode_entities,
+                };
+                if misplaced {
+                    self.errors
+                        .push((ParseErrorKind::MisplacedTableContent, span.location()));
+                    self.foster(node);
+                } else {
+                    self.insert(node);
+                }
+            }
+            token => self.insert(token.into_node(self.spans.then_some(span))),
+        }
+    }
+
+    /// Ignore the rest of the tokens, and close the open elements without
+    /// reporting them, like after an error in the strict mode.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    /// Close all the elements that are still open at the `end` of the html,
+    /// and return the dom with the errors that have been repaired.
+    pub fn finish(mut self, end: Location) -> (Vec<Node<'a>>, Vec<(ParseErrorKind, Location)>) {
+        if self.document && !self.stopped {
+            // What is still open in `head` is closed, so that `body` is
+            // created after it.
+            if !self.has_body {
+                self.close_to(self.open_elements.len().min(1));
+            }
+            self.imply_document(None, None, Span::new(end, end.offset));
+        }
+        self.close_to(self.base);
+        let nodes = match self.open_elements.pop() {
+            Some(context) => context.children,
+            None => self.nodes,
+        };
+        (nodes, self.errors)
+    }
+
+    // Process the end tag `</name>`.
+    fn end(&mut self, name: Cow<'a, str>, span: Span) {
+        if self
+            .too_deep
+            .last()
+            .is_some_and(|dropped| dropped.eq_ignore_ascii_case(&name))
+        {
+            self.too_deep.pop();
+            return;
+        }
+        let tag = Tag::of(&name);
+        // The content after `</body>` and `</html>` belongs to `body` as well,
+        // so they are ignored in a document.
+        if self.document && tags!["body", "html"].has(tag) {
+            return;
+        }
+        if tags!["br", "p"].has(tag) {
+            self.close_foreign_content(tag);
+        }
+        // A `</br>` is treated as `<br>`.
+        if tags!["br"].has(tag) {
+            self.errors.push((
+                ParseErrorKind::UnexpectedEndTag(name.to_string()),
+                span.location(),
+            ));
+            let token = Token::StartTag {
+                name,
+                attrs: Vec::new(),
+                self_closing: false,
+                attr_spans: Vec::new(),
+            };
+            self.push(token, span);
+            return;
+        }
+        let index = match tag {
+            Some(tag) if TABLE.contains(tag) => self.find_in_table_scope(tag.into()),
+            Some(tag) if tags!["p"].contains(tag) => self.find_p_in_scope(),
+            Some(tag) if tags!["li"].contains(tag) => {
+                self.find_in_scope(tag.into(), tags!["ol", "ul"])
+            }
+            Some(tag) => self.find_in_scope(tag.into(), TagSet::EMPTY),
+            None => {
+                let index = self.last_open_name(&name, self.base);
+                self.in_scope(index, TagSet::EMPTY)
+            }
+        };
+        match index {
+            Some(index) => {
+                // Elements opened after it are closed implicitly.
+                self.close_to(index + 1);
+                self.end = span.end;
+                self.close();
+            }
+            None => {
+                self.errors.push((
+                    ParseErrorKind::UnexpectedEndTag(name.to_string()),
+                    span.location(),
+                ));
+                // A stray `</p>` is treated as `<p></p>`.
+                if name.eq_ignore_ascii_case("p") {
+                    let token = Token::StartTag {
+                        name,
+                        attrs: Vec::new(),
+                        self_closing: false,
+                        attr_spans: Vec::new(),
+                    };
+                    let too_deep = self.too_deep.len();
+                    self.push(token, span);
+                    // The `p` is not opened if it is too deep or the limits
+                    // stopped the parsing, and then nothing is closed.
+                    self.too_deep.truncate(too_deep);
+                    let is_opened = self
+                        .open_elements
+                        .last()
+                        .is_some_and(|element| element.span == span && element.tag == tag);
+                    if is_opened {
+                        self.close();
+                    }
+                }
+            }
+        }
+    }
+
+    // Process the token in a document, and return whether it is kept, unlike
+    // a misplaced `<html>`, `<head>` or `<body>`, whose attributes are added
+    // to the first one instead.
+    fn push_document(&mut self, token: &Token<'a>, tag: Option<Tag>, span: Span) -> bool {
+        let (name, attrs, attr_spans) = match token {
+            Token::StartTag {
+                name,
+                attrs,
+                attr_spans,
+                ..
+            } => (name, attrs, attr_spans),
+            Token::Text(text) if !text.trim().is_empty() => {
+                self.imply_document(None, None, span);
+                return true;
+            }
+            // Comments and whitespace stay where they are.
+            _ => return true,
+        };
+        if self.imply_document(Some(name), tag, span) {
+            return true;
+        }
+        self.errors.push((
+            ParseErrorKind::UnexpectedStartTag(name.to_string()),
+            span.location(),
+        ));
+        let index = if tags!["html"].has(tag) { 0 } else { 1 };
+        if let Some(element) = self.open_elements.get_mut(index) {
+            if element.tag == tag {
+                for (key, value) in attrs {
+                    element.attrs.entry(key.clone()).or_insert(value.clone());
+                }
+                for ((key, _), span) in attrs.iter().zip(attr_spans) {
+                    element.attr_spans.entry(key.clone()).or_insert(*span);
+                }
+            }
+        }
+        false
+    }
+
+    // Create the `html`, `head` and `body` elements of a document that are
+    // implied before the start tag `<name>` of the element `tag`, or before
+    // text if `name` is `None`. Return false if the start tag is a
+    // misplaced `html`, `head` or `body`.
+    fn imply_document(&mut self, name: Option<&str>, tag: Option<Tag>, span: Span) -> bool {
+        let is = |tags: TagSet| tags.has(tag);
+        if self.open_elements.is_empty() {
+            if is(tags!["html"]) {
+                return true;
+            }
+            self.open_implied("html", span);
+        }
+        if !self.has_head {
+            self.has_head = true;
+            if is(tags!["head"]) {
+                return true;
+            }
+            self.open_implied("head", span);
+        }
+        if self.has_body || is(tags!["head", "html"]) {
+            return !is(tags!["body", "head", "html"]);
+        }
+        let is_head_tag = is(HEAD);
+        // `head` is closed by the content that cannot be in it.
+        if self.current_is(tags!["head", "noscript"]) && !is_head_tag {
+            self.close_to(1);
+        }
+        if self.open_elements.len() > 1 {
+            return true;
+        }
+        if is_head_tag {
+            // It is moved into the closed `head`.
+            let name = name.unwrap_or_default().to_string();
+            self.errors
+                .push((ParseErrorKind::UnexpectedStartTag(name), span.location()));
+            self.reopen_head();
+            return true;
+        }
+        self.has_body = true;
+        if !is(tags!["body", "frameset"]) {
+            self.open_implied("body", span);
+        }
+        true
+    }
+
+    // Open the element `<name>` implied by the token at `span`.
+    fn open_implied(&mut self, name: &'static str, span: Span) {
+        self.open(OpenElement {
+            name: Cow::Borrowed(name),
+            tag: Tag::of(name),
+            attrs: HashMap::new(),
+            children: Vec::new(),
+            attr_spans: HashMap::new(),
+            namespace: Namespace::Html,
+            fostered: false,
+            span: Span::new(span.location(), span.start),
+            previous: None,
+        });
+    }
+
+    // Open the `head` of the document again after it is closed.
+    fn reopen_head(&mut self) {
+        let html = &mut self.open_elements[0];
+        let index = html.children.iter().rposition(
+            |node| matches!(node, Node::Element { name, .. } if name.eq_ignore_ascii_case("head")),
+        );
+        if let Some(Node::Element {
+            name,
+            attrs,
+            children,
+            span,
+            attr_spans,
+            namespace,
+            ..
+        }) = index.map(|index| html.children.remove(index))
+        {
+            self.open(OpenElement {
+                tag: Tag::of(&name),
+                name,
+                attrs,
+                children,
+                attr_spans,
+                namespace,
+                fostered: false,
+                span: span.unwrap_or(Span::new(Location::start(), 0)),
+                previous: None,
+            });
+        }
+    }
+
+    // Open the `tbody`, `tr` or `colgroup` implied by the start tag of the
+    // element `tag` in a table, or return whether the element is misplaced
+    // in the table, so that it is moved before the table.
+    fn push_table(
+        &mut self,
+        tag: Option<Tag>,
+        attrs: &[(Cow<'a, str>, Cow<'a, str>)],
+        span: Span,
+    ) -> bool {
+        let is = |tags: TagSet| tags.has(tag);
+        if is(TABLE.union(tags!["col", "colgroup"])) {
+            if let Some(index) = self.find_table_context() {
+                // The elements moved before the table are closed.
+                self.close_to(index + 1);
+                if self.current_is(tags!["colgroup"]) && !is(tags!["col"]) {
+                    self.close();
+                }
+                // A table cannot be nested outside of the cells, and
+                // `caption` and `colgroup` belong to the table itself.
+                if is(tags!["caption", "col", "colgroup", "table"]) {
+                    if let Some(index) = self.find_in_table_scope(tags!["table"]) {
+                        let len = if is(tags!["table"]) { index } else { index + 1 };
+                        self.close_to(len);
+                    }
+                }
+                if self.current_or_context_is(tags!["table"]) {
+                    if is(tags!["td", "th", "tr"]) {
+                        self.open_implied("tbody", span);
+                    } else if is(tags!["col"]) {
+                        self.open_implied("colgroup", span);
+                    }
+                }
+                if self.current_or_context_is(tags!["tbody", "tfoot", "thead"])
+                    && is(tags!["td", "th"])
+                {
+                    self.open_implied("tr", span);
+                }
+            }
+            return false;
+        }
+        let hidden = attrs
+            .iter()
+            .find(|(key, _)| key == "type")
+            .is_some_and(|(_, value)| value.eq_ignore_ascii_case("hidden"));
+        let misplaced = self.current_or_context_is(TABLE_CONTEXT)
+            && !is(tags!["form", "script", "style", "template"])
+            && !(is(tags!["input"]) && hidden);
+        if misplaced {
+            self.errors
+                .push((ParseErrorKind::MisplacedTableContent, span.location()));
+        }
+        misplaced
+    }
+
+    // Find the nearest open element of the current table that cannot
+    // contain text, unless a cell or `caption` comes first. The context
+    // element of a fragment counts as well.
+    fn find_table_context(&self) -> Option<usize> {
+        let context = self.base.saturating_sub(1);
+        let index = self.last_open(TABLE_CONTEXT, context)?;
+        let bound = self.last_open(tags!["caption", "html", "td", "template", "th"], context);
+        (bound < Some(index)).then_some(index)
+    }
+
+    // Insert the node misplaced in the current table before the table.
+    fn foster(&mut self, node: Node<'a>) {
+        match self.last_open(tags!["table"], 0) {
+            Some(index) if index > 0 => self.open_elements[index - 1].children.push(node),
+            _ => self.insert(node),
+        }
+    }
+
+    // Close the open elements whose end tags are implied by the start tag
+    // of the element `tag`.
+    fn close_implied_by(&mut self, tag: Option<Tag>) {
+        let is = |tags: TagSet| tags.has(tag);
+        if self.current_is(tags!["head"]) && !is(HEAD) {
+            self.close_to(self.open_elements.len() - 1);
+        }
+        if is(tags!["li"]) {
+            self.close_list_item(tags!["li"]);
+        } else if is(tags!["dd", "dt"]) {
+            self.close_list_item(tags!["dd", "dt"]);
+        } else if is(tags!["option"]) {
+            if self.current_is(tags!["option"]) {
+                self.close();
+            }
+        } else if is(tags!["optgroup"]) {
+            if self.current_is(tags!["option"]) {
+                self.close();
+            }
+            if self.current_is(tags!["optgroup"]) {
+                self.close();
+            }
+        } else if is(tags!["td", "th"]) {
+            self.close_in_table_scope(tags!["td", "th"]);
+        } else if is(tags!["tr"]) {
+            self.close_in_table_scope(tags!["td", "th"]);
+            self.close_in_table_scope(tags!["tr"]);
+        } else if is(tags!["tbody", "tfoot", "thead"]) {
+            self.close_in_table_scope(tags!["td", "th"]);
+            self.close_in_table_scope(tags!["tr"]);
+            self.close_in_table_scope(tags!["tbody", "tfoot", "thead"]);
+        }
+        if is(CLOSING_P) {
+            if let Some(index) = self.find_p_in_scope() {
+                self.close_to(index);
+            }
+        }
+        // Headings cannot be nested.
+        if is(HEADING) && self.current_is(HEADING) {
+            self.close_to(self.open_elements.len() - 1);
+        }
+    }
+
+    // Close the nearest open element in `tags`, unless a special element
+    // other than `address`, `div` and `p` comes first.
+    fn close_list_item(&mut self, tags: TagSet) {
+        let bound = self.special_indices.last().copied();
+        if let Some(index) = self.last_open(tags, self.base) {
+            if bound <= Some(index) {
+                self.close_to(index);
+            }
+        }
+    }
+
+    // Close the nearest open element in `tags` inside the current table.
+    fn close_in_table_scope(&mut self, tags: TagSet) {
+        if let Some(index) = self.find_in_table_scope(tags) {
+            self.close_to(index);
+        }
+    }
+
+    // Find the nearest open element in `tags` inside the current table.
+    fn find_in_table_scope(&self, tags: TagSet) -> Option<usize> {
+        let index = self.last_open(tags, self.base)?;
+        let bound = self.last_open(tags!["html", "table", "template"], 0);
+        (bound <= Some(index)).then_some(index)
+    }
+
+    // Find the nearest open element in `tags`, which is not hidden behind
+    // the scope elements or the extra `scope`.
+    fn find_in_scope(&self, tags: TagSet, scope: TagSet) -> Option<usize> {
+        self.in_scope(self.last_open(tags, self.base), scope)
+    }
+
+    // Check if the open element at `index` is not hidden behind the scope
+    // elements or the extra `scope`.
+    fn in_scope(&self, index: Option<usize>, scope: TagSet) -> Option<usize> {
+        let index = index?;
+        let bound = self
+            .scope_indices
+            .last()
+            .copied()
+            .max(self.last_open(scope, 0));
+        (bound <= Some(index)).then_some(index)
+    }
+
+    fn find_p_in_scope(&self) -> Option<usize> {
+        self.find_in_scope(tags!["p"], tags!["button"])
+    }
+
+    // Find the nearest open element in `tags` from the index `from` of the
+    // stack.
+    fn last_open(&self, tags: TagSet, from: usize) -> Option<usize> {
+        tags.tags()
+            .filter_map(|tag| self.last_open_tags[tag.index()])
+            .max()
+            .filter(|&index| index >= from)
+    }
+
+    // Find the nearest open element named `name`, which is not a known
+    // element, from the index `from` of the stack.
+    fn last_open_name(&self, name: &str, from: usize) -> Option<usize> {
+        let index = *self.last_open_names.get(&*lowercase(name))?;
+        (index >= from).then_some(index)
+    }
+
+    // Whether the start tag of the element `tag` is in svg or math, rather
+    // than in html or in their elements that contain html, like
+    // `foreignObject`. The context element of a fragment counts as well.
+    fn in_foreign_content(&self, tag: Option<Tag>) -> bool {
+        let current = match self.open_elements.last() {
+            Some(current) => current,
+            None => return false,
+        };
+        match current.namespace {
+            Namespace::Html => false,
+            Namespace::Svg => !SVG_HTML.has(current.tag),
+            Namespace::MathMl => {
+                if tags!["mi", "mn", "mo", "ms", "mtext"].has(current.tag) {
+                    tags!["malignmark", "mglyph"].has(tag)
+                } else if tags!["annotation-xml"].has(current.tag) {
+                    let encoding = current.attrs.get("encoding");
+                    !tags!["svg"].has(tag)
+                        && !encoding.is_some_and(|encoding| {
+                            is_one_of(encoding, &["text/html", "application/xhtml+xml"])
+                        })
+                } else {
+                    true
+                }
+            }
+        }
+    }
+
+    /// Check if the current node is html, rather than svg or math.
+    pub fn in_html(&self) -> bool {
+        self.open_elements
+            .last()
+            .is_none_or(|current| current.namespace == Namespace::Html)
+    }
+
+    // Close the open svg and math elements, where the html element `tag`
+    // cannot be.
+    fn close_foreign_content(&mut self, tag: Option<Tag>) {
+        while self.open_elements.len() > self.base && self.in_foreign_content(tag) {
+            self.close_to(self.open_elements.len() - 1);
+        }
+    }
+
+    fn current_is(&self, tags: TagSet) -> bool {
+        self.open_elements[self.base..]
+            .last()
+            .is_some_and(|element| tags.has(element.tag))
+    }
+
+    // Like `current_is`, but the context element of a fragment counts as
+    // well when no element is open in it.
+    fn current_or_context_is(&self, tags: TagSet) -> bool {
+        self.open_elements
+            .last()
+            .is_some_and(|element| tags.has(element.tag))
+    }
+
+    // Check if the text in the element `<name>` is raw, like in `script`.
+    fn is_raw_text(&self, name: &str, tag: Option<Tag>, namespace: Namespace) -> bool {
+        namespace == Namespace::Html && is_in(name, tag, &self.raw_text_tags)
+    }
+
+    // Push the element to the stack of the open elements.
+    fn open(&mut self, mut element: OpenElement<'a>) {
+        let index = self.open_elements.len();
+        // The svg and math elements that contain html are both scope and
+        // special elements, unlike the html ones of the same names.
+        let (scope_tags, special_tags) = match element.namespace {
+            Namespace::Html => (SCOPE, SPECIAL),
+            Namespace::Svg => (SVG_HTML, SVG_HTML),
+            Namespace::MathMl => (MATHML_HTML, MATHML_HTML),
+        };
+        if scope_tags.has(element.tag) {
+            self.scope_indices.push(index);
+        }
+        if special_tags.has(element.tag) {
+            self.special_indices.push(index);
+        }
+        element.previous = match element.tag {
+            Some(tag) => self.last_open_tags[tag.index()].replace(index),
+            None => {
+                let name = match lowercase(&element.name) {
+                    Cow::Borrowed(_) => element.name.clone(),
+                    Cow::Owned(name) => Cow::Owned(name),
+                };
+                self.last_open_names.insert(name, index)
+            }
+        };
+        self.open_elements.push(element);
+    }
+
+    // Append the node to the current open element, or to the top level.
+    fn insert(&mut self, node: Node<'a>) {
+        match self.open_elements.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.nodes.push(node),
+        }
+    }
+
+    // Close the current open element.
+    fn close(&mut self) {
+        if let Some(element) = self.open_elements.pop() {
+            // The elements dropped inside it are closed as well.
+            self.too_deep.clear();
+            let index = self.open_elements.len();
+            match (element.tag, element.previous) {
+                (Some(tag), previous) => self.last_open_tags[tag.index()] = previous,
+                (None, Some(previous)) => {
+                    if let Some(last) = self.last_open_names.get_mut(&*lowercase(&element.name)) {
+                        *last = previous;
+                    }
+                }
+                (None, None) => {
+                    self.last_open_names.remove(&*lowercase(&element.name));
+                }
+            }
+            if self.scope_indices.last() == Some(&index) {
+                self.scope_indices.pop();
+            }
+            if self.special_indices.last() == Some(&index) {
+                self.special_indices.pop();
+            }
+            let span = Span {
+                end: self.end,
+                ..element.span
+            };
+            let (children, content) = if is_template(element.tag, element.namespace) {
+                (Vec::new(), Some(element.children))
+            } else {
+                (element.children, None)
+            };
+            let node = Node::Element {
+                raw_text: self.is_raw_text(&element.name, element.tag, element.namespace),
+                name: element.name,
+                attrs: element.attrs,
+                children,
+                span: self.spans.then_some(span),
+                attr_spans: element.attr_spans,
+                namespace: element.namespace,
+                content,
+                raw_attrs: !self.decode_entities,
+                void: false,
+            };
+            if element.fostered {
+                self.foster(node);
+            } else {
+                self.insert(node);
+            }
+        }
+    }
+
+    // Close the open elements until only `len` of them are left. Those
+    // whose end tags cannot be omitted are reported.
+    fn close_to(&mut self, len: usize) {
+        while self.open_elements.len() > len {
+            if let Some(element) = self.open_elements.last() {
+                if !self.stopped && !OPTIONAL_END.has(element.tag) {
+                    let kind = ParseErrorKind::UnclosedElement(element.name.to_string());
+                    self.errors.push((kind, element.span.location()));
+                }
+            }
+            self.close();
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::{Element, Node};
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{Document, Element, Namespace, Node};
 
 /// Simple query selector
 #[derive(Debug)]
@@ -6,6 +8,7 @@ pub struct Selector {
     class: String,
     id: String,
     tag: String,
+    namespace: Option<Namespace>,
 }
 
 enum SelectorPos {
@@ -19,6 +22,9 @@ impl Selector {
     ///
     /// For example, `div#app`, `span` would be ok, but `.container > div`,
     /// `#app *` would get unexpected results.
+    ///
+    /// The type selector can have a namespace prefix like `svg|a`, and is
+    /// case-insensitive for html elements, but not for svg and MathML ones.
     /// 
     /// ```
     /// use html_query_parser::Selector;
@@ -32,6 +38,9 @@ impl Selector {
     /// let selector = Selector::from("div#app");
     /// let selector = Selector::from("span.info#first");
     /// 
+    /// // Ok: Namespace prefix, which is `html`, `svg` or `math`
+    /// let selector = Selector::from("svg|a");
+    /// 
     /// // Disallowed
     /// let selector = Selector::from("div span");
     /// let selector = Selector::from("a[target=_blank]");
@@ -44,6 +53,7 @@ impl Selector {
             class: String::new(),
             id: String::new(),
             tag: String::new(),
+            namespace: None,
         };
 
         for ch in selector_chars {
@@ -77,6 +87,10 @@ impl Selector {
             SelectorPos::Id => selector.id = string,
             SelectorPos::Tag => selector.tag = string,
         }
+        if let Some(i) = selector.tag.find('|') {
+            selector.namespace = Namespace::from_prefix(&selector.tag[..i]);
+            selector.tag.drain(..=i);
+        }
         selector
     }
 
@@ -88,44 +102,65 @@ impl Selector {
     /// let element: Element = Element::new(
     ///     "div",
     ///     vec![("id", "app")],
-    ///     vec![Node::Text("Hello World!".to_string())],
+    ///     vec![Node::new_text("Hello World!")],
     /// );
     /// 
     /// let selector = Selector::from("div#app");
     /// 
     /// assert_eq!(selector.matches(&element), true);
     /// ```
-    pub fn matches(&self, element: &Element) -> bool {
+    pub fn matches(&self, element: &Element<'_>) -> bool {
+        self.matches_tag(&element.name, &element.attrs, element.namespace)
+    }
+
+    // Check if the element with `name`, `attrs` and `namespace` matches the
+    // `selector`.
+    pub(crate) fn matches_tag(
+        &self,
+        name: &str,
+        attrs: &HashMap<Cow<'_, str>, Cow<'_, str>>,
+        namespace: Namespace,
+    ) -> bool {
         let mut matches = true;
 
-        if self.tag != "" && element.name != self.tag {
+        if !self.tag.is_empty() {
+            let same_tag = match namespace {
+                Namespace::Html => name.eq_ignore_ascii_case(&self.tag),
+                _ => name == self.tag,
+            };
+            if !same_tag {
+                matches = false;
+            }
+        }
+
+        if self.namespace.is_some_and(|ns| ns != namespace) {
             matches = false;
         }
 
-        if self.class != "" {
-            match element.attrs.get("class") {
+        if !self.class.is_empty() {
+            match attrs.get("class") {
                 Some(class) => {
                     if &self.class != class {
                         matches = false;
                     }
                 }
                 None => {
-                    if self.class != "" {
+                    if !self.class.is_empty() {
                         matches = false;
                     }
                 }
             }
         }
 
-        if self.id != "" {
-            match element.attrs.get("id") {
+        if !self.id.is_empty() {
+            match attrs.get("id") {
                 Some(id) => {
                     if &self.id != id {
                         matches = false;
                     }
                 }
                 None => {
-                    if self.id != "" {
+                    if !self.id.is_empty() {
                         matches = false;
                     }
                 }
@@ -137,7 +172,9 @@ impl Selector {
 }
 
 /// Used to `query()` or `query_all()` with `Selector`
-pub trait Queryable {
+///
+/// The elements found borrow from the same html as `self`.
+pub trait Queryable<'a> {
     /// Query the node in `self` for the given selector.
     ///
     /// ```
@@ -158,7 +195,7 @@ pub trait Queryable {
     /// let selector: Selector = Selector::from("#app");
     /// let app: Element = parse(html).query(&selector).unwrap();
     /// ```
-    fn query(&self, selector: &Selector) -> Option<Element>;
+    fn query(&self, selector: &Selector) -> Option<Element<'a>>;
     /// Query all the nodes in `self` for the given selector.
     /// 
     /// ```
@@ -181,37 +218,65 @@ pub trait Queryable {
     /// let selector: Selector = Selector::from(".btn");
     /// let app: Vec<Element> = parse(html).query_all(&selector);
     /// ```
-    fn query_all(&self, selector: &Selector) -> Vec<Element>;
+    fn query_all(&self, selector: &Selector) -> Vec<Element<'a>>;
+}
+
+// Check if the node is an element that matches the `selector`.
+pub(crate) fn matches(selector: &Selector, node: &Node<'_>) -> bool {
+    match node {
+        Node::Element {
+            name,
+            attrs,
+            namespace,
+            ..
+        } => selector.matches_tag(name, attrs, *namespace),
+        _ => false,
+    }
 }
 
-impl Queryable for Vec<Node> {
-    fn query(&self, selector: &Selector) -> Option<Element> {
-        for node in self {
-            if node.is_element() {
-                let element = node.clone().try_into_element().unwrap();
-
-                if selector.matches(&element) {
-                    return Some(element);
-                } else {
-                    if let Some(elem) = element.query(selector) {
-                        return Some(elem);
+pub(crate) fn to_element<'a>(node: &Node<'a>) -> Element<'a> {
+    node.clone().try_into_element().unwrap()
+}
+
+// The descendants are searched without recursion, so that deeply nested
+// nodes cannot overflow the stack.
+impl<'a> Queryable<'a> for Vec<Node<'a>> {
+    fn query(&self, selector: &Selector) -> Option<Element<'a>> {
+        // The children left of the elements being searched.
+        let mut stack = vec![self.iter()];
+        while let Some(nodes) = stack.last_mut() {
+            match nodes.next() {
+                Some(node @ Node::Element { children, .. }) => {
+                    if matches(selector, node) {
+                        return Some(to_element(node));
                     }
+                    stack.push(children.iter());
+                }
+                Some(_) => {}
+                None => {
+                    stack.pop();
                 }
             }
         }
         None
     }
-    fn query_all(&self, selector: &Selector) -> Vec<Element> {
+    fn query_all(&self, selector: &Selector) -> Vec<Element<'a>> {
         let mut elements = Vec::new();
-        for node in self {
-            if node.is_element() {
-                let element = node.clone().try_into_element().unwrap();
-                // Recursively traverse the descendants nodes
-                let sub_elements = element.query_all(selector);
-                elements.extend(sub_elements);
-                // Check if this element matches. If so, push it to the `elements`
-                if selector.matches(&element) {
-                    elements.push(element);
+        // The elements being searched, with their children left.
+        let mut stack = vec![(None, self.iter())];
+        while let Some((_, nodes)) = stack.last_mut() {
+            match nodes.next() {
+                Some(node @ Node::Element { children, .. }) => {
+                    stack.push((Some(node), children.iter()));
+                }
+                Some(_) => {}
+                // The element is pushed after its descendants.
+                None => {
+                    if let Some((Some(node), _)) = stack.pop() {
+                        if matches(selector, node) {
+                            elements.push(to_element(node));
+                        }
+                    }
                 }
             }
         }
@@ -219,11 +284,20 @@ impl Queryable for Vec<Node> {
     }
 }
 
-impl Queryable for Element {
-    fn query(&self, selector: &Selector) -> Option<Element> {
+impl<'a> Queryable<'a> for Element<'a> {
+    fn query(&self, selector: &Selector) -> Option<Element<'a>> {
         self.children.query(selector)
     }
-    fn query_all(&self, selector: &Selector) -> Vec<Element> {
+    fn query_all(&self, selector: &Selector) -> Vec<Element<'a>> {
         self.children.query_all(selector)
     }
 }
+
+impl<'a> Queryable<'a> for Document<'a> {
+    fn query(&self, selector: &Selector) -> Option<Element<'a>> {
+        self.nodes.query(selector)
+    }
+    fn query_all(&self, selector: &Selector) -> Vec<Element<'a>> {
+        self.nodes.query_all(selector)
+    }
+}
@@ -0,0 +1,73 @@
+use crate::data::{
+    HTML_4_01_PUBLIC_ID_PREFIXES, LIMITED_QUIRKS_PUBLIC_ID_PREFIXES, QUIRKS_PUBLIC_IDS,
+    QUIRKS_PUBLIC_ID_PREFIXES,
+};
+
+/// The rendering mode that browsers choose from the doctype.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuirksMode {
+    /// Emulate the legacy browsers, for documents with an old or missing doctype.
+    Quirks,
+    /// Only a few quirks are kept, like for XHTML 1.0 Transitional.
+    LimitedQuirks,
+    /// Follow the standards, like for `<!DOCTYPE html>`.
+    NoQuirks,
+}
+
+fn starts_with_ignore_case(id: &str, prefix: &str) -> bool {
+    id.len() >= prefix.len()
+        && id.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+impl QuirksMode {
+    /// Compute the mode from the name, public identifier and system
+    /// identifier of a doctype.
+    ///
+    /// ```
+    /// use html_query_parser::QuirksMode;
+    ///
+    /// assert_eq!(QuirksMode::from_doctype(Some("html"), None, None), QuirksMode::NoQuirks);
+    /// let public_id = "-//W3C//DTD HTML 4.01 Transitional//EN";
+    /// assert_eq!(
+    ///     QuirksMode::from_doctype(Some("html"), Some(public_id), None),
+    ///     QuirksMode::Quirks
+    /// );
+    /// ```
+    pub fn from_doctype(
+        name: Option<&str>,
+        public_id: Option<&str>,
+        system_id: Option<&str>,
+    ) -> Self {
+        if name != Some("html") {
+            return QuirksMode::Quirks;
+        }
+        if system_id.is_some_and(|id| {
+            id.eq_ignore_ascii_case("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        }) {
+            return QuirksMode::Quirks;
+        }
+        let public_id = match public_id {
+            Some(public_id) => public_id,
+            None => return QuirksMode::NoQuirks,
+        };
+        let starts_with_any = |prefixes: &[&str]| {
+            prefixes
+                .iter()
+                .any(|prefix| starts_with_ignore_case(public_id, prefix))
+        };
+        if QUIRKS_PUBLIC_IDS
+            .iter()
+            .any(|id| public_id.eq_ignore_ascii_case(id))
+            || starts_with_any(&QUIRKS_PUBLIC_ID_PREFIXES)
+            || (system_id.is_none() && starts_with_any(&HTML_4_01_PUBLIC_ID_PREFIXES))
+        {
+            QuirksMode::Quirks
+        } else if starts_with_any(&LIMITED_QUIRKS_PUBLIC_ID_PREFIXES)
+            || (system_id.is_some() && starts_with_any(&HTML_4_01_PUBLIC_ID_PREFIXES))
+        {
+            QuirksMode::LimitedQuirks
+        } else {
+            QuirksMode::NoQuirks
+        }
+    }
+}
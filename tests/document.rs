@@ -0,0 +1,66 @@
+use html_query_parser::{parse_document, Editable, Htmlifiable, Node, Queryable, Selector};
+
+const HTML: &str = r#"
+    <!DOCTYPE html>
+    <html lang="en">
+    <head>
+        <meta http-equiv="Content-Type" content="text/html; charset=ISO-8859-1">
+        <base target="_blank"><base href="https://example.com/">
+        <title>
+            Hello
+            World
+        </title>
+    </head>
+    <body>
+        <div id="app"><span>Hi</span></div>
+    </body>
+    </html>"#;
+
+#[test]
+fn document_accessors() {
+    let document = parse_document(HTML);
+    assert!(matches!(document.doctype(), Some(Node::Doctype { .. })));
+    assert_eq!(document.html_element().unwrap().name, "html");
+    assert_eq!(document.head().unwrap().name, "head");
+    let body = document.body().unwrap();
+    assert!(body.query(&Selector::from("#app")).is_some());
+    assert_eq!(document.title().unwrap(), "Hello World");
+    assert_eq!(document.base_url(), Some("https://example.com/"));
+    assert_eq!(document.lang(), Some("en"));
+    assert_eq!(document.charset(), Some("ISO-8859-1"));
+
+    let document = parse_document(r#"<meta charset=" utf-8 "><p>Hi"#);
+    assert!(document.doctype().is_none());
+    assert!(document.title().is_none());
+    assert!(document.base_url().is_none());
+    assert!(document.lang().is_none());
+    assert_eq!(document.charset(), Some("utf-8"));
+}
+
+#[test]
+fn document_set_title() {
+    let mut document = parse_document(HTML);
+    document.set_title("Hi & Bye");
+    assert_eq!(document.title().unwrap(), "Hi & Bye");
+    assert!(document.html().contains("<title>Hi &amp; Bye</title>"));
+
+    let mut document = parse_document("<p>Hi");
+    document.set_title("App");
+    assert_eq!(
+        document.html(),
+        "<html><head><title>App</title></head><body><p>Hi</p></body></html>"
+    );
+}
+
+#[test]
+fn document_edit() {
+    let mut document = parse_document(HTML).trim();
+    document
+        .insert_to(&Selector::from("#app"), Node::new_text("!"))
+        .remove_by(&Selector::from("span"));
+    assert_eq!(
+        document.query(&Selector::from("#app")).unwrap().html(),
+        r#"<div id="app">!</div>"#
+    );
+    assert_eq!(document.query_all(&Selector::from("base")).len(), 2);
+}
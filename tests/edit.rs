@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use html_query_parser::{parse, Editable, Htmlifiable, Node, Selector};
+use html_query_parser::{parse, Editable, Htmlifiable, Namespace, Node, Queryable, Selector};
 
 const HTML: &str = r#"
     <!DOCTYPE html>
@@ -39,9 +39,16 @@ const REMOVED_HTML: &str = r#"
 fn insert() {
     let body_selector = Selector::from("body");
     let script = Node::Element {
-        name: "script".to_string(),
+        name: "script".into(),
         attrs: HashMap::new(),
-        children: vec![Node::Text(r#"console.log("Hello World")"#.to_string())],
+        children: vec![Node::new_text(r#"console.log("Hello World")"#)],
+        span: None,
+        attr_spans: HashMap::new(),
+        namespace: Namespace::Html,
+        content: None,
+        raw_attrs: false,
+        void: false,
+        raw_text: true,
     };
     let html = parse(HTML).insert_to(&body_selector, script).html();
     assert_eq!(html, INSERTED_HTML);
@@ -53,3 +60,61 @@ fn remove() {
     let html = parse(HTML).remove_by(&meta_selector).html();
     assert_eq!(html, REMOVED_HTML);
 }
+
+#[test]
+fn template() {
+    let html =
+        r#"<template id="row"><li class="ad"></li><li>Name</li></template><li class="ad"></li>"#;
+    let mut nodes = parse(html);
+    let ad_selector = Selector::from(".ad");
+    assert_eq!(nodes.query_all(&ad_selector).len(), 1);
+
+    nodes.remove_by(&ad_selector);
+    assert_eq!(
+        nodes.html(),
+        r#"<template id="row"><li class="ad"></li><li>Name</li></template>"#
+    );
+
+    let template_selector = Selector::from("template");
+    nodes
+        .insert_to(&Selector::from("li"), Node::new_text("!"))
+        .edit_content(&template_selector, |content| {
+            content.remove_by(&ad_selector);
+        });
+    assert_eq!(
+        nodes.html(),
+        r#"<template id="row"><li>Name</li></template>"#
+    );
+
+    let template = nodes.query(&template_selector).unwrap();
+    assert!(template.children.is_empty());
+    assert_eq!(template.content.unwrap().query_all(&ad_selector).len(), 0);
+}
+
+// The implementors outside the crate do not have to implement the methods
+// added later, like `edit_content`.
+struct Fragment(Vec<Node<'static>>);
+
+impl Editable<'static> for Fragment {
+    fn trim(self) -> Self {
+        Fragment(self.0.trim())
+    }
+
+    fn insert_to(&mut self, selector: &Selector, target: Node<'static>) -> &mut Self {
+        self.0.insert_to(selector, target);
+        self
+    }
+
+    fn remove_by(&mut self, selector: &Selector) -> &mut Self {
+        self.0.remove_by(selector);
+        self
+    }
+}
+
+#[test]
+fn default_edit_content() {
+    let nodes = parse("<template><p>Row</p></template>").into_iter();
+    let mut fragment = Fragment(nodes.map(Node::into_owned).collect());
+    fragment.edit_content(&Selector::from("template"), |content| content.clear());
+    assert_eq!(fragment.0.html(), "<template><p>Row</p></template>");
+}
@@ -1,4 +1,4 @@
-use html_query_parser::{parse, Htmlifiable, Editable};
+use html_query_parser::{parse, parse_with, Editable, Htmlifiable, Node, ParseOptions, Selector};
 
 const HTML: &str = r#"
     <div>
@@ -20,3 +20,153 @@ fn trimmed_html() {
         r#"<div><span id="class">Hello</span><span class="id">World</span></div>"#
     );
 }
+
+#[test]
+fn escaped_html() {
+    let html = r#"<p title="&quot;a&quot; &amp; b">&lt;x&gt; &amp;&nbsp;y</p>"#;
+    assert_eq!(parse(html).html(), html);
+
+    let html = "<script>if (a < b && c) {}</script><style>a > b {}</style>";
+    assert_eq!(parse(html).html(), html);
+}
+
+#[test]
+fn doctype_html() {
+    for html in [
+        "<!DOCTYPE html>",
+        r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd">"#,
+        r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN">"#,
+        r#"<!DOCTYPE html SYSTEM "about:legacy-compat">"#,
+    ] {
+        assert_eq!(parse(html).html(), html);
+    }
+}
+
+#[test]
+fn cdata_and_processing_instruction_html() {
+    let html = r#"<?xml version="1.0" encoding="UTF-8"?><math><![CDATA[a<b>c&d]]></math>"#;
+    assert_eq!(parse(html).html(), html);
+}
+
+const TEMPLATE: &str = "<!doctype html>
+<HTML lang=en>
+<head>
+\t<meta charset=utf-8>
+\t<title>Tom &amp; Jerry</title>
+\t<script>if (a < b && c) {}</script>
+</head>
+<body>
+\t<!-- Menu -->
+\t<ul class='menu'
+\t    id=\"menu\">
+\t\t<li><a href=/ class=active>Home</a>
+\t\t<li><a href='/about' >About</a>
+\t</ul>
+\t<p>Fish &amp chips<br/>&nbsp;
+\t<table><tr><td>1</td></tr></table>
+\t<textarea><b>&lt;</textarea>
+\t<svg viewBox=\"0 0 8 8\"><clipPath id=c /><path d=M0/></svg>
+</body>
+</HTML>
+";
+
+#[test]
+fn lossless_html() {
+    let options = ParseOptions {
+        spans: true,
+        ..ParseOptions::default()
+    };
+    let mut nodes = parse_with(TEMPLATE, &options);
+    assert_eq!(nodes.lossless_html(TEMPLATE), TEMPLATE);
+
+    // Only the edited nodes are converted again, and the end tags omitted
+    // before them are added.
+    let li = Node::new_element("li", vec![], vec![]);
+    nodes.insert_to(&Selector::from("ul"), li);
+    nodes.remove_by(&Selector::from("table"));
+    let html = nodes.lossless_html(TEMPLATE);
+    assert_eq!(
+        html,
+        TEMPLATE
+            .replace("About</a>\n\t</ul>", "About</a>\n\t</li><li></li></ul>")
+            .replace("\n\t<table><tr><td>1</td></tr></table>", "\n\t</p>")
+    );
+
+    // The start tag of the edited element is converted again, but the nodes
+    // inside it and its end tag are kept.
+    let children = nodes.iter_mut().find_map(|node| match node {
+        Node::Element { name, children, .. } if name == "HTML" => Some(children),
+        _ => None,
+    });
+    let children = children.unwrap();
+    let body = children.iter_mut().rev().find(|node| node.is_element());
+    if let Some(Node::Element { attrs, .. }) = body {
+        attrs.insert("class".into(), "dark".into());
+    }
+    let html = nodes.lossless_html(TEMPLATE);
+    assert!(html.contains("</head>\n<body class=\"dark\">\n\t<!-- Menu -->\n"));
+    assert!(html.ends_with("</svg>\n</body>\n</HTML>\n"));
+}
+
+#[test]
+fn lossless_html_attributes() {
+    let options = ParseOptions {
+        spans: true,
+        ..ParseOptions::default()
+    };
+    let html = "<a x=1 Z='3'  y=\"2\">Link</a>";
+    for _ in 0..8 {
+        let mut nodes = parse_with(html, &options);
+        if let Node::Element { attrs, .. } = &mut nodes[0] {
+            attrs.remove("y");
+            attrs.insert("x".into(), "4".into());
+            attrs.insert("w".into(), "5".into());
+            attrs.insert("v".into(), "6".into());
+        }
+        assert_eq!(
+            nodes.lossless_html(html),
+            "<a x=\"4\" Z='3' v=\"6\" w=\"5\">Link</a>"
+        );
+    }
+
+    // The implementors outside the crate get `html()` by default.
+    struct Text(&'static str);
+    impl Htmlifiable for Text {
+        fn html(&self) -> String {
+            self.0.to_string()
+        }
+    }
+    assert_eq!(Text("<p>").lossless_html("<p>"), "<p>");
+}
+
+#[test]
+fn lossless_html_references() {
+    let options = ParseOptions {
+        spans: true,
+        ..ParseOptions::default()
+    };
+    // The text around the dropped end tag would make a new reference.
+    for (html, lossless) in [
+        ("&</b>lt", "&amp;lt"),
+        ("<p>&div</b>;", "<p>&amp;div;"),
+        ("a&#3</i>9;", "a\u{3}9;"),
+        ("&amp</b>x", "&amp;x"),
+        ("&</b><br>", "&<br>"),
+        ("&</b> lt", "& lt"),
+    ] {
+        let nodes = parse_with(html, &options);
+        assert_eq!(nodes.lossless_html(html), lossless);
+        assert_eq!(parse(lossless).html(), parse(html).html());
+    }
+
+    // The text of `textarea` in svg is not RCDATA.
+    let html = "<svg><textarea></i></&'</";
+    let lossless = parse_with(html, &options).lossless_html(html);
+    assert_eq!(parse(&lossless).html(), parse(html).html());
+
+    // So would a new text node.
+    let html = "<p>&amp</p>";
+    let mut nodes = parse_with(html, &options);
+    nodes.insert_to(&Selector::from("p"), Node::new_text("x"));
+    assert_eq!(nodes.lossless_html(html), "<p>&amp;x</p>");
+}
@@ -1,4 +1,11 @@
-use html_query_parser::{parse, Editable};
+use std::borrow::Cow;
+
+use html_query_parser::{
+    parse, parse_bytes, parse_document, parse_fragment, parse_reader, parse_with,
+    parse_with_errors, try_parse, try_parse_with, Editable, Encoding, Htmlifiable, LimitPolicy,
+    Limits, Namespace, Node, ParseErrorKind, ParseOptions, Parser, Queryable, QuirksMode, Selector,
+    Span,
+};
 
 #[test]
 fn paired_tag() {
@@ -34,6 +41,90 @@ fn comment_tag() {
     println!("{:#?}", b);
 }
 
+#[test]
+fn comment_edge_cases() {
+    let comments = |html: &str| {
+        let (nodes, errors) = parse_with_errors(html);
+        let nodes: Vec<_> = nodes
+            .iter()
+            .map(|node| match node {
+                Node::Comment(comment, _) => format!("<!--{}-->", comment),
+                node => node.html(),
+            })
+            .collect();
+        let errors: Vec<_> = errors.into_iter().map(|err| err.kind).collect();
+        (nodes.concat(), errors)
+    };
+    assert_eq!(
+        comments("<!-->a"),
+        ("<!---->a".into(), vec![ParseErrorKind::AbruptComment])
+    );
+    assert_eq!(
+        comments("<!--->a"),
+        ("<!---->a".into(), vec![ParseErrorKind::AbruptComment])
+    );
+    assert_eq!(
+        comments("<!---- x ---->a"),
+        ("<!---- x ---->a".into(), vec![])
+    );
+    assert_eq!(comments("<!--a--!-->b"), ("<!--a--!-->b".into(), vec![]));
+    assert_eq!(
+        comments("<!-- a --!>b"),
+        (
+            "<!-- a -->b".into(),
+            vec![ParseErrorKind::IncorrectlyClosedComment]
+        )
+    );
+    assert_eq!(
+        comments("<?foo>a"),
+        ("<!--?foo-->a".into(), vec![ParseErrorKind::BogusComment])
+    );
+    assert_eq!(
+        comments(r#"<!ELEMENT a "b>c">"#),
+        (
+            r#"<!--ELEMENT a "b-->c"&gt;"#.into(),
+            vec![ParseErrorKind::BogusComment]
+        )
+    );
+    assert_eq!(
+        comments("<!a<b>c"),
+        ("<!--a<b-->c".into(), vec![ParseErrorKind::BogusComment])
+    );
+    assert_eq!(
+        comments("<!>"),
+        ("<!---->".into(), vec![ParseErrorKind::BogusComment])
+    );
+
+    // Comments not closed take the rest of the html.
+    assert_eq!(
+        comments("<p>a<!--b<p>c--"),
+        (
+            "<p>a<!--b<p>c--></p>".into(),
+            vec![ParseErrorKind::UnclosedComment]
+        )
+    );
+    assert_eq!(
+        comments("<!--a---"),
+        ("<!--a--->".into(), vec![ParseErrorKind::UnclosedComment])
+    );
+    assert_eq!(
+        comments("<!DOC"),
+        ("<!--DOC-->".into(), vec![ParseErrorKind::BogusComment])
+    );
+    assert_eq!(
+        comments("<?a b"),
+        ("<!--?a b-->".into(), vec![ParseErrorKind::BogusComment])
+    );
+
+    // The same comments are found however the html is split.
+    let html = "<!-- a --!><?b?><?c><!d><!--e";
+    let mut parser = Parser::new();
+    for i in 0..html.len() {
+        parser.feed(&html[i..i + 1]);
+    }
+    assert_eq!(parser.finish().html(), parse(html).html());
+}
+
 #[test]
 fn attributes() {
     let a = parse("<img src=\"example.png\" alt=example>");
@@ -43,6 +134,43 @@ fn attributes() {
     println!("{:#?}", b);
 }
 
+#[test]
+fn attribute_edge_cases() {
+    let attrs = |html: &str| {
+        let mut attrs: Vec<_> = match parse(html).remove(0) {
+            Node::Element { attrs, .. } => attrs
+                .into_iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect(),
+            node => panic!("expected an element, got {:?}", node),
+        };
+        attrs.sort();
+        attrs
+    };
+    assert_eq!(attrs("<div\tclass='a'\r\nid=b\x0C>"), ["class=a", "id=b"]);
+    assert_eq!(attrs("<a b = 'c' d= e f =g>"), ["b=c", "d=e", "f=g"]);
+    assert_eq!(attrs("<a b/c d='e'/f>"), ["b=", "c=", "d=e", "f="]);
+    assert_eq!(
+        attrs("<a =b @click=x :prop='y' x-on:click=z [attr]>"),
+        [":prop=y", "=b=", "@click=x", "[attr]=", "x-on:click=z"]
+    );
+    // Quotes only start values, and `/` is in unquoted values.
+    assert_eq!(attrs(r#"<a b"c='d' e="C:\">"#), [r#"b"c=d"#, r"e=C:\"]);
+    assert_eq!(attrs("<a href=/about/>"), ["href=/about/"]);
+
+    // The first of the duplicated attributes wins.
+    assert_eq!(attrs("<a b=1 B=2 c b=3>"), ["b=1", "c="]);
+    let (_, errors) = parse_with_errors("<a b=1 B=2 c b=3></a>");
+    let errors: Vec<_> = errors.into_iter().map(|err| err.kind).collect();
+    assert_eq!(
+        errors,
+        [
+            ParseErrorKind::DuplicateAttribute("B".to_string()),
+            ParseErrorKind::DuplicateAttribute("b".to_string()),
+        ]
+    );
+}
+
 #[test]
 fn matched() {
     let a = parse(
@@ -102,3 +230,819 @@ fn complex() {
 
     println!("{:#?}", a);
 }
+
+#[test]
+fn parse_errors() {
+    let err = try_parse("<div>\n    <a b=\"c\"d></a>\n</div>").unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidAttributes);
+    assert_eq!((err.offset, err.line, err.column), (10, 2, 5));
+
+    let err = try_parse("<p></p></p>").unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::UnexpectedEndTag("p".to_string()));
+    assert_eq!((err.offset, err.line, err.column), (7, 1, 8));
+
+    let err = try_parse("<!-->").unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::AbruptComment);
+
+    let err = try_parse("<br>一二</ >").unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidTag);
+    assert_eq!((err.offset, err.line, err.column), (10, 1, 7));
+}
+
+#[test]
+fn no_errors() {
+    assert!(try_parse("<p>1 > 0</p>").is_ok());
+    assert!(try_parse("<p>é</p><x-é />").is_ok());
+}
+
+#[test]
+fn unclosed_and_invalid_tags() {
+    // A tag cut off by the end of the html is dropped with an error.
+    let err = try_parse("<p>a</p><span class=\"x").unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::EofInTag);
+    assert_eq!(err.offset, 8);
+    let (nodes, errors) = parse_with_errors("<p>a</p><span class=\"x");
+    assert_eq!(nodes.html(), "<p>a</p>");
+    assert_eq!(errors.len(), 1);
+
+    // `<` not followed by a letter, `/`, `!` or `?` is kept in the text.
+    let err = try_parse("1 < 2").unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidTag);
+    assert_eq!(err.offset, 2);
+    for html in ["1 < 2", "a < b and c > d", "<1 b>", "a <", "x <= y"] {
+        let nodes = parse(html);
+        assert!(
+            matches!(&nodes[..], [Node::Text { text, .. }] if text == html),
+            "{}",
+            html
+        );
+    }
+    let nodes = parse("<p>a <b> c</p>");
+    assert_eq!(nodes.html(), "<p>a <b> c</b></p>");
+}
+
+#[test]
+fn recovered() {
+    let html = |html: &str| parse(html).html();
+    assert_eq!(html("<div><span></div>"), "<div><span></span></div>");
+    assert_eq!(
+        html("<div><p><span></p>x</div>"),
+        "<div><p><span></span></p>x</div>"
+    );
+    assert_eq!(html("</span><div></div></div>"), "<div></div>");
+    assert_eq!(html("<div><span>Hello"), "<div><span>Hello</span></div>");
+    assert_eq!(html("<DIV></div>"), "<DIV></DIV>");
+    assert_eq!(html("<input disabled>"), "<input disabled=\"\">");
+    let a = parse("<a b=\"c\"d>").query(&Selector::from("a")).unwrap();
+    let b = parse("<a b=\"c\" d>").query(&Selector::from("a")).unwrap();
+    assert_eq!(a.attrs, b.attrs);
+    assert_eq!(html("<!-->"), "<!---->");
+    // A `</br>` is treated as `<br>`, while the other stray end tags are
+    // dropped.
+    assert_eq!(html("a</br>b</BR>c"), "a<br>b<BR>c");
+    assert_eq!(html("<p>a</br></p>"), "<p>a<br></p>");
+    assert_eq!(html("<svg></br>a"), "<svg/><br>a");
+
+    let (_, errors) = parse_with_errors("<ul>\n<li><b>a</li>\n</ol></br></ul>");
+    let errors: Vec<_> = errors
+        .into_iter()
+        .map(|err| (err.kind, err.line, err.column))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::UnclosedElement("b".to_string()), 2, 5),
+            (ParseErrorKind::UnexpectedEndTag("ol".to_string()), 3, 1),
+            (ParseErrorKind::UnexpectedEndTag("br".to_string()), 3, 6),
+        ]
+    );
+}
+
+#[test]
+fn implied_end_tags() {
+    let html = |html: &str| parse(html).html();
+    assert_eq!(html("<ul><li>a<li>b</ul>"), "<ul><li>a</li><li>b</li></ul>");
+    assert_eq!(html("<p>a<p>b<div>c</div>"), "<p>a</p><p>b</p><div>c</div>");
+    assert_eq!(
+        html("<dl><dt>a<dd>b<dt>c</dl>"),
+        "<dl><dt>a</dt><dd>b</dd><dt>c</dt></dl>"
+    );
+    assert_eq!(
+        html("<select><option>a<optgroup><option>b</select>"),
+        "<select><option>a</option><optgroup><option>b</option></optgroup></select>"
+    );
+    assert_eq!(
+        html("<table><tr><td>a<td>b<tr><th>c</table>"),
+        "<table><tr><td>a</td><td>b</td></tr><tr><th>c</th></tr></table>"
+    );
+    assert_eq!(
+        html("<ul><li><ul><li>a</ul><li>b</ul>"),
+        "<ul><li><ul><li>a</li></ul></li><li>b</li></ul>"
+    );
+    assert_eq!(
+        html("<html><head><title>a</title><body><p>b</html>"),
+        "<html><head><title>a</title></head><body><p>b</p></body></html>"
+    );
+    assert_eq!(html("<h1>a<h2>b</h2>"), "<h1>a</h1><h2>b</h2>");
+    assert_eq!(
+        html("<p><button><div>a</div></button>"),
+        "<p><button><div>a</div></button></p>"
+    );
+    assert_eq!(html("a</p>"), "a<p></p>");
+
+    // Omitting these end tags is not an error.
+    let (_, errors) = parse_with_errors("<table><tr><td><p>a<li>b</table>");
+    assert!(errors.is_empty());
+    let (_, errors) = parse_with_errors("<h1>a<h2>b</h2>");
+    let errors: Vec<_> = errors.into_iter().map(|err| err.kind).collect();
+    assert_eq!(
+        errors,
+        vec![ParseErrorKind::UnclosedElement("h1".to_string())]
+    );
+}
+
+#[test]
+fn raw_text() {
+    let nodes = parse("<script>if (a < b && c > d) { x = '</div>'; }</script>");
+    let script = nodes[0].clone().try_into_element().unwrap();
+    assert_eq!(script.children.len(), 1);
+    match &script.children[0] {
+        Node::Text { text, .. } => assert_eq!(text, "if (a < b && c > d) { x = '</div>'; }"),
+        node => panic!("unexpected node: {:?}", node),
+    }
+
+    let html = |html: &str| parse(html).html();
+    assert_eq!(html("<style>a > b { }</style>"), "<style>a > b { }</style>");
+    assert_eq!(
+        html("<xmp><p>a</p></xmp><p>b</p>"),
+        "<xmp><p>a</p></xmp><p>b</p>"
+    );
+    assert_eq!(
+        html("<SCRIPT>a</scripts></Script >"),
+        "<SCRIPT>a</scripts></SCRIPT>"
+    );
+    assert_eq!(html("<script></script>"), "<script></script>");
+    assert_eq!(html("<script><!-- a"), "<script><!-- a</script>");
+    let noframes = parse("<noframes><p>a</p></noframes>")
+        .query(&Selector::from("noframes"))
+        .unwrap();
+    assert_eq!(noframes.children.len(), 1);
+}
+
+#[test]
+fn rcdata() {
+    let nodes = parse("<title>a <b> c</title><textarea><p>d</textarea>");
+    for (node, content) in nodes.into_iter().zip(["a <b> c", "<p>d"]) {
+        let element = node.try_into_element().unwrap();
+        match &element.children[..] {
+            [Node::Text { text, .. }] => assert_eq!(text, content),
+            children => panic!("unexpected children: {:?}", children),
+        }
+    }
+    assert_eq!(parse("<TITLE>a</title >b").html(), "<TITLE>a</TITLE>b");
+}
+
+#[test]
+fn character_references() {
+    let text = |html: &str| match &parse(html)[..] {
+        [Node::Text { text, .. }] => text.to_string(),
+        nodes => panic!("unexpected nodes: {:?}", nodes),
+    };
+    assert_eq!(text("a &amp; b &lt;&gt; &quot;"), "a & b <> \"");
+    assert_eq!(
+        text("&#x27;&#39;&#X41;&#0;&#x110000;&#xD800;"),
+        "''A\u{FFFD}\u{FFFD}\u{FFFD}"
+    );
+    assert_eq!(text("&#128;&#x9F;"), "\u{20AC}\u{178}");
+    assert_eq!(
+        text("&nbsp;&nbsp&copy2&notit;&notin;"),
+        "\u{A0}\u{A0}\u{A9}2\u{AC}it;\u{2209}"
+    );
+    assert_eq!(text("AT&T &unknown; & &# &#x;"), "AT&T &unknown; & &# &#x;");
+    assert_eq!(text("&CounterClockwiseContourIntegral;"), "\u{2233}");
+    assert_eq!(text("&NotEqualTilde;"), "\u{2242}\u{338}");
+
+    let a = parse(r#"<a href="?a=1&copy=2&amp;b=3&copy;" title='&lt;&gt'>&lt;b&gt;</a>"#)
+        .query(&Selector::from("a"))
+        .unwrap();
+    assert_eq!(a.attrs["href"], "?a=1&copy=2&b=3\u{A9}");
+    assert_eq!(a.attrs["title"], "<>");
+    assert!(matches!(&a.children[..], [Node::Text { text, .. }] if text == "<b>"));
+
+    assert!(matches!(
+        &parse("<title>&lt;b&gt;</title>")[0],
+        Node::Element { children, .. } if matches!(&children[..], [Node::Text { text, .. }] if text == "<b>")
+    ));
+    assert!(matches!(
+        &parse("<script>&lt;</script>")[0],
+        Node::Element { children, .. } if matches!(&children[..], [Node::Text { text, .. }] if text == "&lt;")
+    ));
+
+    let (_, errors) = parse_with_errors("&amp &unknown; &#;\n<a b='&#0;'></a>");
+    let errors: Vec<_> = errors
+        .into_iter()
+        .map(|err| (err.kind, err.line, err.column))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (ParseErrorKind::InvalidCharacterReference, 1, 1),
+            (ParseErrorKind::InvalidCharacterReference, 1, 6),
+            (ParseErrorKind::InvalidCharacterReference, 1, 16),
+            (ParseErrorKind::InvalidCharacterReference, 2, 1),
+        ]
+    );
+}
+
+#[test]
+fn raw_character_references() {
+    let options = ParseOptions {
+        decode_entities: false,
+        ..ParseOptions::default()
+    };
+    let a = parse_with(r#"<a title="&lt;">&amp;</a>"#, &options)
+        .query(&Selector::from("a"))
+        .unwrap();
+    assert_eq!(a.attrs["title"], "&lt;");
+    assert!(matches!(&a.children[..], [Node::Text { text, .. }] if text == "&amp;"));
+
+    // They are written back as they are, not escaped again.
+    let html = r#"<p title="a&amp;b">x &lt; y &amp z</p>1 &gt; 0<textarea>&lt;</textarea>"#;
+    assert_eq!(parse_with(html, &options).html(), html);
+    let html = r#"<p title='say "hi"'>x</p>"#;
+    assert_eq!(parse_with(html, &options).html(), html);
+}
+
+#[test]
+fn doctype() {
+    let nodes = parse(
+        r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 4.01//EN" "http://www.w3.org/TR/html4/strict.dtd">"#,
+    );
+    match &nodes[0] {
+        Node::Doctype {
+            name,
+            public_id,
+            system_id,
+            ..
+        } => {
+            assert_eq!(name.as_deref(), Some("html"));
+            assert_eq!(public_id.as_deref(), Some("-//W3C//DTD HTML 4.01//EN"));
+            assert_eq!(
+                system_id.as_deref(),
+                Some("http://www.w3.org/TR/html4/strict.dtd")
+            );
+        }
+        node => panic!("expected a doctype, got {:?}", node),
+    }
+    assert_eq!(nodes[0].quirks_mode(), Some(QuirksMode::NoQuirks));
+
+    let quirks_mode = |html: &str| parse(html)[0].quirks_mode();
+    assert_eq!(quirks_mode("<!doctype html>"), Some(QuirksMode::NoQuirks));
+    assert_eq!(
+        quirks_mode(r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01 Frameset//EN">"#),
+        Some(QuirksMode::Quirks)
+    );
+    assert_eq!(
+        quirks_mode(r#"<!DOCTYPE html SYSTEM "about:legacy-compat">"#),
+        Some(QuirksMode::NoQuirks)
+    );
+    assert_eq!(quirks_mode("<!DOCTYPE svg>"), Some(QuirksMode::Quirks));
+    assert_eq!(quirks_mode("<p></p>"), None);
+
+    let (nodes, errors) = parse_with_errors("<!DOCTYPE html PUBLIC><!ELEMENT br EMPTY>");
+    assert!(matches!(&nodes[1], Node::Comment(comment, _) if comment == "ELEMENT br EMPTY"));
+    assert_eq!(errors[0].kind, ParseErrorKind::InvalidDoctype);
+    assert_eq!(errors[1].kind, ParseErrorKind::BogusComment);
+}
+
+#[test]
+fn cdata_and_processing_instructions() {
+    let nodes = parse(r#"<?xml version="1.0"?><svg><![CDATA[x < y && "a>b"]]></svg><?>"#);
+    assert!(matches!(
+        &nodes[0],
+        Node::ProcessingInstruction { target, data, .. } if target == "xml" && data == r#"version="1.0""#
+    ));
+    let svg = nodes.query(&Selector::from("svg")).unwrap();
+    assert!(matches!(&svg.children[..], [Node::CData(text, _)] if text == r#"x < y && "a>b""#));
+    // `<?` is closed by the first `>`, as a bogus comment if not by `?>`.
+    assert!(matches!(&nodes[2], Node::Comment(comment, _) if comment == "?"));
+
+    let nodes = parse("<div>\n<?php echo 1 ?>\n<![CDATA[]]>\n</div>").trim();
+    assert_eq!(nodes.html(), "<div><?php echo 1 ?><![CDATA[]]></div>");
+}
+
+#[test]
+fn streaming() {
+    let html = "<!DOCTYPE html><title>A &amp; B</title>\n<script>if (a</b) {}</script>\
+        <!-- <p> --><p title='x > y'>你好 &notit;<br></div><![CDATA[]]>";
+    let (nodes, errors) = parse_with_errors(html);
+    for chunk_size in 1..8 {
+        let mut parser = Parser::new();
+        for chunk in html.as_bytes().chunks(chunk_size) {
+            parser.feed(chunk);
+        }
+        let (streamed_nodes, streamed_errors) = parser.finish_with_errors();
+        assert_eq!(streamed_nodes.html(), nodes.html());
+        assert_eq!(streamed_errors, errors);
+    }
+
+    let nodes = parse_reader(html.as_bytes()).unwrap();
+    assert_eq!(nodes.html(), parse(html).html());
+
+    let mut parser = Parser::new();
+    parser.feed(b"<p>\xFFa\xE4");
+    parser.feed("</p>");
+    assert_eq!(parser.finish().html(), "<p>\u{FFFD}a\u{FFFD}</p>");
+}
+
+#[test]
+fn spans() {
+    let options = ParseOptions {
+        spans: true,
+        ..ParseOptions::default()
+    };
+    let html = "<div id=app>\n  <p class='a'  hidden>One<p id=x id=y>Two</div>\n</ul>";
+    let nodes = parse_with(html, &options);
+    let source = |span: Option<Span>| &html[span.unwrap().range()];
+
+    let div = nodes.query(&Selector::from("#app")).unwrap();
+    assert_eq!(source(div.span), &html[..html.find("\n</ul>").unwrap()]);
+    let p = div.query(&Selector::from("p")).unwrap();
+    assert_eq!(source(p.span), "<p class='a'  hidden>One");
+    assert_eq!(source(p.attr_spans.get("class").copied()), "class='a'");
+    let hidden = p.attr_spans["hidden"];
+    assert_eq!(&html[hidden.range()], "hidden");
+    assert_eq!((hidden.line, hidden.column), (2, 17));
+    assert_eq!(source(p.children[0].span()), "One");
+
+    let x = div.query(&Selector::from("#x")).unwrap();
+    assert_eq!(source(x.span), "<p id=x id=y>Two");
+    assert_eq!(source(x.attr_spans.get("id").copied()), "id=x");
+
+    let nodes = parse(html);
+    let div = nodes.query(&Selector::from("#app")).unwrap();
+    assert_eq!(div.span, None);
+    assert!(div.attr_spans.is_empty());
+    assert_eq!(div.children[0].span(), None);
+}
+
+#[test]
+fn deeply_nested() {
+    let depth = 5_000;
+    let html = "<div><b>".repeat(depth) + &"</b></div>".repeat(depth);
+    let options = ParseOptions {
+        limits: Limits {
+            max_depth: usize::MAX,
+            ..Limits::default()
+        },
+        ..ParseOptions::default()
+    };
+    let mut parser = Parser::with_options(&options);
+    parser.feed(&html);
+    let (nodes, errors) = parser.finish_with_errors();
+    assert!(errors.is_empty());
+    // They are not converted or queried recursively.
+    assert_eq!(nodes.html(), html);
+    assert!(nodes.query(&Selector::from("i")).is_none());
+    assert!(nodes.query_all(&Selector::from("i")).is_empty());
+    // Nor edited.
+    let selector = Selector::from("b");
+    let mut nodes = nodes.trim();
+    nodes
+        .insert_to(&selector, Node::new_text("x"))
+        .remove_by(&Selector::from("i"))
+        .edit_content(&selector, |_| {});
+    assert_eq!(nodes.html(), html.replace("</b>", "x</b>"));
+    let mut nodes = &nodes;
+    let mut found = 0;
+    while let [Node::Element { children, .. }, ..] = &nodes[..] {
+        nodes = children;
+        found += 1;
+    }
+    assert_eq!(found, depth * 2);
+}
+
+#[test]
+fn borrowed() {
+    let html = String::from(r#"<div id="app"><p class="a">Tom &amp; Jerry</p></div>"#);
+    let nodes = parse(&html);
+    let p = nodes.query(&Selector::from(".a")).unwrap();
+    assert!(matches!(p.name, Cow::Borrowed("p")));
+    assert!(matches!(p.attrs["class"], Cow::Borrowed("a")));
+    // Decoded text cannot be borrowed.
+    assert!(
+        matches!(&p.children[..], [Node::Text { text: Cow::Owned(text), .. }] if text == "Tom & Jerry")
+    );
+
+    let nodes: Vec<Node<'static>> = nodes.into_iter().map(Node::into_owned).collect();
+    drop(html);
+    assert_eq!(
+        nodes.html(),
+        r#"<div id="app"><p class="a">Tom &amp; Jerry</p></div>"#
+    );
+}
+
+#[test]
+fn options() {
+    let options = ParseOptions {
+        keep_comments: false,
+        keep_whitespace: false,
+        ..ParseOptions::default()
+    };
+    let html = "<ul>\n  <!-- One -->\n  <li>Two</li>\n</ul>\n<pre> </pre>";
+    assert_eq!(
+        parse_with(html, &options).html(),
+        "<ul><li>Two</li></ul><pre></pre>"
+    );
+
+    let options = ParseOptions {
+        lowercase_names: true,
+        spans: true,
+        ..ParseOptions::default()
+    };
+    let div = parse_with("<DIV ID=app Class=a></Div>", &options)
+        .query(&Selector::from("div#app"))
+        .unwrap();
+    assert_eq!(div.attrs["class"], "a");
+    assert!(div.attr_spans.contains_key("class"));
+    assert!(parse_with("<DIV></Div>", &options)[0].span().is_some());
+
+    let options = ParseOptions {
+        void_tags: vec!["br".to_string(), "x-icon".to_string()],
+        raw_text_tags: vec!["script".to_string(), "code".to_string()],
+        ..ParseOptions::default()
+    };
+    let nodes = parse_with("<x-icon><img><code><b>&lt;</code></img>", &options);
+    assert!(
+        matches!(&nodes[0], Node::Element { name, children, .. } if name == "x-icon" && children.is_empty())
+    );
+    let img = nodes.query(&Selector::from("img")).unwrap();
+    let code = img.query(&Selector::from("code")).unwrap();
+    assert!(matches!(&code.children[..], [Node::Text { text, .. }] if text == "<b>&lt;"));
+    // They are written back like they are parsed, also in uppercase.
+    let html = "<X-ICON><img><CODE><b>&lt;</CODE></img><BR>";
+    assert_eq!(parse_with(html, &options).html(), html);
+
+    let (nodes, errors) = parse_with_errors("<P>a<BR>b</P>c");
+    assert_eq!(nodes.html(), "<P>a<BR>b</P>c");
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn strict() {
+    let options = ParseOptions {
+        strict: true,
+        ..ParseOptions::default()
+    };
+    let html = "<p>One</p></div><p>Two</p>";
+    assert_eq!(parse_with(html, &options).html(), "<p>One</p>");
+    let err = try_parse_with(html, &options).unwrap_err();
+    assert_eq!(
+        err.kind,
+        ParseErrorKind::UnexpectedEndTag("div".to_string())
+    );
+    assert_eq!(err.offset, 10);
+
+    // The open elements are closed without being reported.
+    let html = "<div><p>One<a b=\"c\"d>Two</a></p>";
+    assert_eq!(parse_with(html, &options).html(), "<div><p>One</p></div>");
+    let err = try_parse_with(html, &options).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidAttributes);
+    assert_eq!(err.offset, 11);
+
+    let mut parser = Parser::with_options(&options);
+    parser.feed("<div><span>");
+    parser.feed("</div><p>Ignored</p>");
+    let (nodes, errors) = parser.finish_with_errors();
+    assert_eq!(nodes.html(), "<div><span></span></div>");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].kind,
+        ParseErrorKind::UnclosedElement("span".to_string())
+    );
+
+    assert!(try_parse_with("<p>1 > 0</p>", &options).is_ok());
+}
+
+#[test]
+fn limits() {
+    // Elements nested too deep are dropped by default, but not their content.
+    let html = "<div>".repeat(600) + "Hi" + &"</div>".repeat(600);
+    let (nodes, errors) = parse_with_errors(&html);
+    assert_eq!(
+        nodes.html(),
+        "<div>".repeat(512) + "Hi" + &"</div>".repeat(512)
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ParseErrorKind::TooDeep);
+    assert_eq!(errors[0].offset, 512 * 5);
+
+    // The `<p>` of a stray `</p>` too deep is dropped without closing its parent.
+    let options = ParseOptions {
+        limits: Limits {
+            max_depth: 2,
+            ..Limits::default()
+        },
+        ..ParseOptions::default()
+    };
+    let html = "<div><span></p>x</span>y</div>z";
+    assert_eq!(
+        parse_with(html, &options).html(),
+        "<div><span>x</span>y</div>z"
+    );
+
+    let options = ParseOptions {
+        limits: Limits {
+            max_depth: 2,
+            policy: LimitPolicy::Error,
+            ..Limits::default()
+        },
+        ..ParseOptions::default()
+    };
+    let html = "<div><p><b>One</b>Two</p></div><p>Three</p>";
+    assert_eq!(parse_with(html, &options).html(), "<div><p></p></div>");
+    let err = try_parse_with(html, &options).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::TooDeep);
+    assert_eq!(err.offset, 8);
+
+    // The rest of the html is dropped after too many nodes.
+    let options = ParseOptions {
+        limits: Limits {
+            max_nodes: 3,
+            ..Limits::default()
+        },
+        ..ParseOptions::default()
+    };
+    let mut parser = Parser::with_options(&options);
+    parser.feed("<ul><li>One</li><li>Two</li></ul>");
+    let (nodes, errors) = parser.finish_with_errors();
+    assert_eq!(nodes.html(), "<ul><li>One</li></ul>");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ParseErrorKind::TooManyNodes);
+    assert_eq!(errors[0].offset, 16);
+
+    let options = ParseOptions {
+        limits: Limits {
+            max_attrs: 2,
+            max_attr_len: 3,
+            max_text_len: 4,
+            ..Limits::default()
+        },
+        ..ParseOptions::default()
+    };
+    let mut parser = Parser::with_options(&options);
+    parser.feed(r#"<a href="/about" title="About" id=x>Hello</a><!--Comment--><p>héllo</p>"#);
+    let (nodes, errors) = parser.finish_with_errors();
+    let a = nodes[0].clone().try_into_element().unwrap();
+    assert_eq!(a.attrs.len(), 2);
+    assert_eq!(a.attrs["href"], "/ab");
+    assert_eq!(a.attrs["title"], "Abo");
+    assert_eq!(a.children.html(), "Hell");
+    assert_eq!(nodes[1..].to_vec().html(), "<!--Comm--><p>hél</p>");
+    let kinds: Vec<_> = errors.into_iter().map(|error| error.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            ParseErrorKind::TooManyAttributes,
+            ParseErrorKind::AttributeTooLong,
+            ParseErrorKind::AttributeTooLong,
+            ParseErrorKind::TextTooLong,
+            ParseErrorKind::TextTooLong,
+            ParseErrorKind::TextTooLong,
+        ]
+    );
+
+    // The input is cut off as if it ended there.
+    let options = ParseOptions {
+        limits: Limits {
+            max_input_len: 10,
+            ..Limits::default()
+        },
+        ..ParseOptions::default()
+    };
+    let mut parser = Parser::with_options(&options);
+    parser.feed("<p>Hel");
+    parser.feed("lo</p><p>World</p>");
+    let (nodes, errors) = parser.finish_with_errors();
+    assert_eq!(nodes.html(), "<p>Hello</p>");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ParseErrorKind::InputTooLarge);
+    assert_eq!(errors[0].offset, 10);
+}
+
+#[test]
+fn encodings() {
+    let (nodes, encoding) = parse_bytes(b"\xEF\xBB\xBF<p>\xE4\xB8\x96</p>");
+    assert_eq!(encoding, Encoding::Utf8);
+    assert_eq!(nodes.html(), "<p>\u{4E16}</p>");
+
+    let (nodes, encoding) = parse_bytes(b"\xFE\xFF\0<\0p\0>\x4E\x16\xD8");
+    assert_eq!(encoding, Encoding::Utf16Be);
+    assert_eq!(encoding.name(), "UTF-16BE");
+    assert_eq!(nodes.html(), "<p>\u{4E16}\u{FFFD}</p>");
+
+    let html = b"<meta http-equiv=Content-Type content=\"text/html; charset='latin1'\">\
+        <p>Caf\xE9</p>";
+    let (nodes, encoding) = parse_bytes(html);
+    assert_eq!(encoding, Encoding::Windows1252);
+    assert_eq!(nodes[1].html(), "<p>Caf\u{E9}</p>");
+
+    // `content` is ignored without `http-equiv`.
+    let html = b"<meta content=\"text/html; charset=latin1\"><p>Caf\xC3\xA9</p>";
+    assert_eq!(parse_bytes(html).1, Encoding::Utf8);
+    let (_, encoding) = parse_bytes(b"<p>Caf\xE9</p>");
+    assert_eq!(encoding, Encoding::Windows1252);
+
+    // The comments and the attributes of other tags are skipped.
+    let html = b"<!-- <meta charset=latin1> --><i title=\"<meta charset=latin1>\"></i>\
+        <meta Charset=UTF-8>Caf\xE9";
+    let (nodes, encoding) = parse_bytes(html);
+    assert_eq!(encoding, Encoding::Utf8);
+    assert_eq!(nodes[3].html(), "Caf\u{FFFD}");
+
+    // UTF-16 cannot be declared in the html that is read as ascii.
+    assert_eq!(parse_bytes(b"<meta charset=utf-16>").1, Encoding::Utf8);
+
+    // Only the first 1024 bytes are prescanned.
+    let html = [&[b' '; 1024][..], b"<meta charset=latin1>\xC3\xA9"].concat();
+    assert_eq!(parse_bytes(&html).1, Encoding::Utf8);
+}
+
+#[test]
+fn namespaces() {
+    let html = r#"<div><svg viewbox="0 0 8 8"><path d="M0 0"/><foreignobject><p>Hi</foreignobject></svg></div>"#;
+    let nodes = parse(html);
+    assert_eq!(
+        nodes.html(),
+        r#"<div><svg viewBox="0 0 8 8"><path d="M0 0"/><foreignObject><p>Hi</p></foreignObject></svg></div>"#
+    );
+    let svg = nodes.query(&Selector::from("svg")).unwrap();
+    assert_eq!(svg.namespace, Namespace::Svg);
+    let path = nodes.query(&Selector::from("path")).unwrap();
+    assert_eq!(path.namespace, Namespace::Svg);
+    assert!(path.children.is_empty());
+    let p = nodes.query(&Selector::from("p")).unwrap();
+    assert_eq!(p.namespace, Namespace::Html);
+
+    // Svg names are case-sensitive in selectors, unlike html ones.
+    assert!(nodes.query(&Selector::from("foreignObject")).is_some());
+    assert!(nodes.query(&Selector::from("foreignobject")).is_none());
+    assert!(nodes.query(&Selector::from("DIV")).is_some());
+    assert!(nodes.query(&Selector::from("svg|p")).is_none());
+    assert!(nodes.query(&Selector::from("html|p")).is_some());
+
+    // Html elements break out of svg and math.
+    let (nodes, errors) = parse_with_errors("<svg><g><p>Hi</p></svg>");
+    assert_eq!(nodes.html(), "<svg><g/></svg><p>Hi</p>");
+    assert_eq!(
+        errors[0].kind,
+        ParseErrorKind::UnclosedElement("svg".to_string())
+    );
+
+    // MathML text elements can contain html.
+    let html = r#"<math definitionurl="/"><mi><b>x</b></mi><mi><mglyph/></mi></math>"#;
+    let nodes = parse(html);
+    assert_eq!(
+        nodes.html(),
+        r#"<math definitionURL="/"><mi><b>x</b></mi><mi><mglyph/></mi></math>"#
+    );
+    let b = nodes.query(&Selector::from("b")).unwrap();
+    assert_eq!(b.namespace, Namespace::Html);
+    let mglyph = nodes.query(&Selector::from("math|mglyph")).unwrap();
+    assert_eq!(mglyph.namespace, Namespace::MathMl);
+
+    // Svg and math elements named like raw text or RCDATA ones have markup.
+    let html = "<svg><style><![CDATA[.a{fill:red}]]></style><title>A<b>B</b></title></svg>";
+    let nodes = parse(html);
+    let style = nodes.query(&Selector::from("svg|style")).unwrap();
+    assert!(matches!(&style.children[0], Node::CData(text, ..) if text == ".a{fill:red}"));
+    let b = nodes.query(&Selector::from("b")).unwrap();
+    assert_eq!(b.namespace, Namespace::Html);
+    assert_eq!(nodes.html(), html);
+    let mut parser = Parser::new();
+    for i in 0..html.len() {
+        parser.feed(&html[i..i + 1]);
+    }
+    assert_eq!(parser.finish().html(), html);
+    let nodes = parse("<svg><foreignObject><style>a<b</style></foreignObject></svg>");
+    let style = nodes.query(&Selector::from("style")).unwrap();
+    assert!(matches!(&style.children[0], Node::Text { text, .. } if text == "a<b"));
+
+    // The svg and math elements that contain html bound the scope, so that
+    // the html in them cannot close what is open outside.
+    let cases = [
+        (
+            "<p>a<svg><foreignObject><p>b</p></foreignObject></svg>c</p>",
+            "<p>a<svg><foreignObject><p>b</p></foreignObject></svg>c</p>",
+        ),
+        (
+            "<p>a<svg><title><p>b</p></title></svg>c</p>",
+            "<p>a<svg><title><p>b</p></title></svg>c</p>",
+        ),
+        (
+            "<li>a<svg><desc><li>b",
+            "<li>a<svg><desc><li>b</li></desc></svg></li>",
+        ),
+        (
+            "<p>a<math><mi></p>",
+            "<p>a<math><mi><p></p></mi></math></p>",
+        ),
+        (
+            "<dd>a<math><mtext><dt>b</mtext></math>c",
+            "<dd>a<math><mtext><dt>b</dt></mtext></math>c</dd>",
+        ),
+        (
+            r#"<p>a<math><annotation-xml encoding="text/html"><p>b</p></annotation-xml></math>c"#,
+            r#"<p>a<math><annotation-xml encoding="text/html"><p>b</p></annotation-xml></math>c</p>"#,
+        ),
+    ];
+    for (html, expected) in cases {
+        assert_eq!(parse(html).html(), expected, "{html}");
+    }
+    // Other svg elements do not.
+    assert_eq!(
+        parse("<p>a<svg><g></p>b").html(),
+        "<p>a<svg><g/></svg></p>b"
+    );
+}
+
+#[test]
+fn fragments() {
+    // The context cannot be closed or implied to end.
+    let nodes = parse_fragment("<li>One<li>Two</ul><li>Three", "ul");
+    assert_eq!(nodes.html(), "<li>One</li><li>Two</li><li>Three</li>");
+    let nodes = parse_fragment("<p>One<p>Two</p></p>", "p");
+    assert_eq!(nodes.html(), "<p>One</p><p>Two</p><p></p>");
+    let nodes = parse_fragment("<option>One<option>Two", "option");
+    assert_eq!(nodes.html(), "<option>One</option><option>Two</option>");
+
+    // The elements implied in tables are opened in a table context too.
+    let nodes = parse_fragment("<tr><td>x", "table");
+    assert_eq!(nodes.html(), "<tbody><tr><td>x</td></tr></tbody>");
+    let nodes = parse_fragment("<td>x<td>y", "tbody");
+    assert_eq!(nodes.html(), "<tr><td>x</td><td>y</td></tr>");
+    let nodes = parse_fragment("<td>x", "tr");
+    assert_eq!(nodes.html(), "<td>x</td>");
+    let nodes = parse_fragment("<td>x", "div");
+    assert_eq!(nodes.html(), "<td>x</td>");
+
+    // The content of raw text and RCDATA elements is all text.
+    let nodes = parse_fragment("if (a </script> b) {}", "script");
+    assert!(matches!(&nodes[0], Node::Text { text, .. } if text == "if (a </script> b) {}"));
+    let nodes = parse_fragment("<b>Tom &amp; Jerry</b>", "TITLE");
+    assert!(matches!(&nodes[0], Node::Text { text, .. } if text == "<b>Tom & Jerry</b>"));
+    let nodes = parse_fragment("<b>Tom &amp; Jerry</b>", "div");
+    assert_eq!(nodes.html(), "<b>Tom &amp; Jerry</b>");
+
+    // The content of svg is svg, unless it can only be html.
+    let nodes = parse_fragment(r#"<clippath/><p>Hi</p>"#, "svg");
+    let clip_path = nodes[0].clone().try_into_element().unwrap();
+    assert_eq!(clip_path.name, "clipPath");
+    assert_eq!(clip_path.namespace, Namespace::Svg);
+    let p = nodes[1].clone().try_into_element().unwrap();
+    assert_eq!(p.namespace, Namespace::Html);
+}
+
+#[test]
+fn documents() {
+    // The same page with and without the optional tags.
+    let a = parse_document("<!DOCTYPE html><title>App</title><p>Hello");
+    let b = parse_document(
+        "<!DOCTYPE html><html><head><title>App</title></head><body><p>Hello</p></body></html>",
+    );
+    assert_eq!(a.nodes.html(), b.nodes.html());
+    assert_eq!(
+        a.nodes.html(),
+        "<!DOCTYPE html><html><head><title>App</title></head><body><p>Hello</p></body></html>"
+    );
+    assert_eq!(
+        parse_document("").nodes.html(),
+        "<html><head></head><body></body></html>"
+    );
+
+    // The elements of `head` after it are moved into it, and the attributes
+    // of a misplaced `html` or `body` are added to the first one.
+    let html = r#"<head></head><meta charset="utf-8"><p>Hi<body class="a"><html lang="en">"#;
+    assert_eq!(
+        parse_document(html).nodes.html(),
+        r#"<html lang="en"><head><meta charset="utf-8"></head><body class="a"><p>Hi</p></body></html>"#
+    );
+
+    // The content after `</body>` belongs to `body`.
+    let html = "<body><p>One</p></body></html><p>Two</p>";
+    assert_eq!(
+        parse_document(html).nodes.html(),
+        "<html><head></head><body><p>One</p><p>Two</p></body></html>"
+    );
+
+    // The text and elements misplaced in a table are moved before it, and
+    // `tbody` and `tr` are implied.
+    let html = "<table>Oops<b>!</b><td>1<tr><td>2</table>";
+    let document = parse_document(html);
+    assert_eq!(
+        document.nodes.html(),
+        "<html><head></head><body>Oops<b>!</b><table><tbody><tr><td>1</td></tr><tr><td>2</td></tr></tbody></table></body></html>"
+    );
+    let table = document.nodes.query(&Selector::from("table")).unwrap();
+    assert_eq!(table.query_all(&Selector::from("tr")).len(), 2);
+}
@@ -0,0 +1,135 @@
+use std::borrow::Cow;
+
+use html_query_parser::{ParseErrorKind, ParseOptions, Span, Token, Tokenizer};
+
+#[test]
+fn tokens() {
+    let html = "<!doctype html><p class=a>Hi<br/></p><!--c--><style>p>a{}</style>";
+    let tokens: Vec<Token> = Tokenizer::new(html).map(|(token, _)| token).collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Doctype {
+                name: Some("html".into()),
+                public_id: None,
+                system_id: None,
+            },
+            Token::StartTag {
+                name: "p".into(),
+                attrs: vec![("class".into(), "a".into())],
+                self_closing: false,
+                attr_spans: vec![Span {
+                    start: 18,
+                    end: 25,
+                    line: 1,
+                    column: 19,
+                }],
+            },
+            Token::Text("Hi".into()),
+            Token::StartTag {
+                name: "br".into(),
+                attrs: Vec::new(),
+                self_closing: true,
+                attr_spans: Vec::new(),
+            },
+            Token::EndTag { name: "p".into() },
+            Token::Comment("c".into()),
+            Token::StartTag {
+                name: "style".into(),
+                attrs: Vec::new(),
+                self_closing: false,
+                attr_spans: Vec::new(),
+            },
+            Token::Text("p>a{}".into()),
+            Token::EndTag {
+                name: "style".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn spans() {
+    let html = "<p>\n  你好 &amp;<br></p>\n<title>x</title >";
+    let slices: Vec<&str> = Tokenizer::new(html)
+        .map(|(_, span)| &html[span.range()])
+        .collect();
+    assert_eq!(
+        slices,
+        [
+            "<p>",
+            "\n  你好 &amp;",
+            "<br>",
+            "</p>",
+            "\n",
+            "<title>",
+            "x",
+            "</title >"
+        ]
+    );
+    let (_, span) = Tokenizer::new(html).nth(2).unwrap();
+    assert_eq!((span.line, span.column), (2, 11));
+}
+
+#[test]
+fn attribute_spans() {
+    let html = "<a id=x\n  ID=y title=\"é >\" href=/>";
+    let (token, _) = Tokenizer::new(html).next().unwrap();
+    let Token::StartTag {
+        attrs, attr_spans, ..
+    } = token
+    else {
+        panic!("{token:?}");
+    };
+    // The duplicated `ID` is dropped along with its span.
+    let keys: Vec<_> = attrs.iter().map(|(key, _)| key.as_ref()).collect();
+    assert_eq!(keys, ["id", "title", "href"]);
+    let spans: Vec<_> = attr_spans
+        .iter()
+        .map(|span| (&html[span.range()], span.line, span.column))
+        .collect();
+    assert_eq!(
+        spans,
+        [("id=x", 1, 4), ("title=\"é >\"", 2, 8), ("href=/", 2, 20)]
+    );
+}
+
+#[test]
+fn tokenizer_errors() {
+    let options = ParseOptions {
+        decode_entities: false,
+        ..ParseOptions::default()
+    };
+    let mut tokenizer = Tokenizer::with_options("<>&x;</ >\n<a b='c'd>", &options);
+    // `<>` is kept in the text.
+    assert_eq!(tokenizer.by_ref().count(), 4);
+    let errors: Vec<_> = tokenizer
+        .take_errors()
+        .into_iter()
+        .map(|err| (err.kind, err.line, err.column))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (ParseErrorKind::InvalidTag, 1, 1),
+            (ParseErrorKind::InvalidTag, 1, 6),
+            (ParseErrorKind::InvalidAttributes, 2, 1),
+        ]
+    );
+}
+
+#[test]
+fn borrowed_tokens() {
+    let html = "<a href='/a?b=1&amp;c=2'>A &lt; B</a>";
+    let tokens: Vec<Token> = Tokenizer::new(html).map(|(token, _)| token).collect();
+    let Token::StartTag { name, attrs, .. } = &tokens[0] else {
+        panic!("{:?}", tokens[0]);
+    };
+    assert!(matches!(name, Cow::Borrowed("a")));
+    // Decoded texts cannot be borrowed.
+    assert!(matches!(&attrs[0], (_, Cow::Owned(href)) if href == "/a?b=1&c=2"));
+    assert!(matches!(&tokens[1], Token::Text(Cow::Owned(text)) if text == "A < B"));
+
+    let owned: Vec<Token<'static>> = tokens.into_iter().map(Token::into_owned).collect();
+    assert_eq!(owned[2], Token::EndTag { name: "a".into() });
+}